@@ -0,0 +1,114 @@
+/*
+Copyright (c) 2022 Todd Stellanova
+LICENSE: BSD3 (see LICENSE file)
+*/
+
+//! Byte ring buffers over caller-provided memory.
+//!
+//! Only the index and lock bookkeeping lives in the [`SliceRingu`] itself,
+//! so the bytes can sit wherever the caller puts them: a `#[link_section]`
+//! static in a DMA-reachable SRAM bank, a `.dtcm` region, and so on.
+
+use crate::lock::unlocked;
+use crate::sync::const_fn;
+use crate::{Overflow, RingBuf, RingLock, SpinLock};
+
+/// A byte ring buffer whose storage is borrowed rather than owned.
+///
+/// ```
+/// use ringu::SliceRingu;
+///
+/// #[link_section = ".data.rx"]
+/// static mut RX_BYTES: [u8; 256] = [0; 256];
+/// // Safety: nothing else ever touches RX_BYTES
+/// static RX: SliceRingu<'static> =
+///     SliceRingu::from_array(unsafe { &mut *core::ptr::addr_of_mut!(RX_BYTES) });
+///
+/// assert_eq!(RX.push_one(0x55), 1);
+/// assert_eq!(RX.capacity(), 256);
+/// ```
+pub type SliceRingu<'a, L = SpinLock> = RingBuf<&'a mut [u8], L>;
+
+impl<'a> SliceRingu<'a> {
+    const_fn! {
+        /// Use all of `buf` as the storage.
+        /// This is a `const fn`, so the buffer can be placed in a plain `static`.
+        /// `N` must be a non-zero power of two; anything else fails to compile.
+        pub fn from_array<const N: usize>(buf: &'a mut [u8; N]) -> Self {
+            Self::from_array_with_lock(buf, SpinLock::new_with_spin(crate::sync::spin_loop), Overflow::Reject)
+        }
+    }
+
+    /// Use the longest power-of-two prefix of `buf` as the storage.
+    ///
+    /// # Panics
+    ///
+    /// If `buf` is empty.
+    pub fn from_slice(buf: &'a mut [u8]) -> Self {
+        Self::from_slice_with_lock(buf, unlocked(), Overflow::Reject)
+    }
+}
+
+impl<'a, L: RingLock> SliceRingu<'a, L> {
+    const_fn! {
+        /// Use all of `buf` as the storage, guarded by `lock` and with the given overflow policy.
+        /// `N` must be a non-zero power of two; anything else fails to compile.
+        pub fn from_array_with_lock<const N: usize>(buf: &'a mut [u8; N], lock: L, overflow: Overflow) -> Self {
+            const { assert!(N.is_power_of_two(), "SliceRingu capacity N must be a non-zero power of two") };
+            Self::with_storage(buf, N, lock, overflow)
+        }
+    }
+
+    /// Use the longest power-of-two prefix of `buf` as the storage,
+    /// guarded by `lock` and with the given overflow policy.
+    ///
+    /// # Panics
+    ///
+    /// If `buf` is empty.
+    pub fn from_slice_with_lock(buf: &'a mut [u8], lock: L, overflow: Overflow) -> Self {
+        Self::from_storage_with_lock(buf, lock, overflow)
+    }
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uses_power_of_two_prefix() {
+        let mut bytes = [0xaa; 100];
+        let bffl = SliceRingu::from_slice(&mut bytes);
+        assert_eq!(bffl.capacity(), 64);
+        assert_eq!(bffl.push_slice(&[1; 80]), 64);
+        drop(bffl);
+        assert_eq!(bytes[63], 1);
+        assert_eq!(bytes[64], 0xaa);
+    }
+
+    #[test]
+    fn bytes_land_in_callers_array() {
+        let mut bytes = [0x55; 4];
+        let bffl = SliceRingu::from_array(&mut bytes);
+        // whatever the memory held before isn't data
+        assert!(bffl.empty());
+        assert_eq!(bffl.push_slice(&[1, 2, 3]), 3);
+        assert_eq!(bffl.read_slice(&mut [0; 2]), 2);
+        // wraps around the end of the array
+        assert_eq!(bffl.push_slice(&[4, 5]), 2);
+        drop(bffl);
+        assert_eq!(bytes, [5, 2, 3, 4]);
+    }
+
+    #[test]
+    fn write_grants_in_borrowed_memory() {
+        let mut bytes = [0; 8];
+        let mut bffl = SliceRingu::from_slice(&mut bytes);
+        let (mut producer, mut consumer) = bffl.split();
+        let mut grant = producer.grant_write(3);
+        grant.buf().copy_from_slice(b"dma");
+        grant.commit(3);
+        let grant = consumer.grant_read();
+        assert_eq!(grant.bufs(), (&b"dma"[..], &[][..]));
+        grant.release(3);
+    }
+}
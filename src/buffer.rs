@@ -0,0 +1,110 @@
+/*
+Copyright (c) 2022 Todd Stellanova
+LICENSE: BSD3 (see LICENSE file)
+*/
+
+//! The [`RingBuffer`] trait, for code that works with any ring buffer.
+
+use crate::{Full, RingBuf, RingLock, Storage};
+
+/// The push and read API shared by every [`RingBuf`], whatever its storage or lock,
+/// so the same code can drive a [`Ringu`](crate::Ringu) or a `HeapRingu`.
+pub trait RingBuffer {
+    /// The element type
+    type Item;
+
+    /// How many elements the buffer can hold
+    fn capacity(&self) -> usize;
+
+    /// How much data is available to be read?
+    fn available(&self) -> usize;
+
+    /// At the moment, how much vacant space remains in the buffer?
+    fn vacant(&self) -> usize;
+
+    /// Is the buffer full?
+    fn full(&self) -> bool;
+
+    /// Is the buffer empty?
+    fn empty(&self) -> bool;
+
+    /// Push one element, applying the buffer's overflow policy if it is full
+    fn try_push(&self, item: Self::Item) -> Result<(), Full<Self::Item>>;
+
+    /// Pop the oldest element, if any
+    fn try_pop(&self) -> Option<Self::Item>;
+
+    /// Push as many elements from `src` as fit, returning how many were pushed
+    fn push_slice(&self, src: &[Self::Item]) -> usize where Self::Item: Copy;
+
+    /// Read up to `dst.len()` elements into `dst`, returning how many were read
+    fn read_slice(&self, dst: &mut [Self::Item]) -> usize where Self::Item: Copy;
+}
+
+impl<T, S: Storage<Item = T>, L: RingLock> RingBuffer for RingBuf<S, L> {
+    type Item = T;
+
+    fn capacity(&self) -> usize {
+        RingBuf::capacity(self)
+    }
+
+    fn available(&self) -> usize {
+        RingBuf::available(self)
+    }
+
+    fn vacant(&self) -> usize {
+        RingBuf::vacant(self)
+    }
+
+    fn full(&self) -> bool {
+        RingBuf::full(self)
+    }
+
+    fn empty(&self) -> bool {
+        RingBuf::empty(self)
+    }
+
+    fn try_push(&self, item: T) -> Result<(), Full<T>> {
+        RingBuf::try_push(self, item)
+    }
+
+    fn try_pop(&self) -> Option<T> {
+        RingBuf::try_pop(self)
+    }
+
+    fn push_slice(&self, src: &[T]) -> usize where T: Copy {
+        RingBuf::push_slice(self, src)
+    }
+
+    fn read_slice(&self, dst: &mut [T]) -> usize where T: Copy {
+        RingBuf::read_slice(self, dst)
+    }
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use super::*;
+    use crate::Ringu;
+
+    /// Fill and drain any byte ring through the trait alone
+    fn round_trip<R: RingBuffer<Item = u8>>(bffl: &R) {
+        let cap = bffl.capacity();
+        let src: Vec<u8> = (0..cap as u8).collect();
+        assert_eq!(bffl.push_slice(&src), cap);
+        assert!(bffl.full());
+        assert_eq!(bffl.try_push(0), Err(Full(0)));
+        assert_eq!(bffl.try_pop(), Some(0));
+        assert_eq!(bffl.vacant(), 1);
+        let mut dst = vec![0; cap];
+        assert_eq!(bffl.read_slice(&mut dst), cap - 1);
+        assert_eq!(&dst[..cap - 1], &src[1..]);
+        assert!(bffl.empty());
+    }
+
+    #[test]
+    fn generic_over_storage() {
+        round_trip(&Ringu::<u8, 8>::new());
+        #[cfg(feature = "alloc")]
+        round_trip(&crate::HeapRingu::<u8>::with_capacity(20));
+    }
+}
@@ -0,0 +1,208 @@
+/*
+Copyright (c) 2022 Todd Stellanova
+LICENSE: BSD3 (see LICENSE file)
+*/
+
+//! [`embedded-io`](embedded_io) (and, with the `embedded-io-async` feature,
+//! [`embedded-io-async`](embedded_io_async)) traits for byte buffers.
+//!
+//! The traits are implemented for `&RingBuf<S, L>`, so a buffer in a `static`
+//! can be handed to a driver as `&mut &RING`, while another context holds
+//! the other end. (Through `&mut RingBuf` nothing else could ever fill or drain
+//! the buffer, so a blocking `read` or `write` would wait forever.)
+//! As the traits require, `read` and `write` block until at least one byte
+//! moves; use `ReadReady` / `WriteReady` to avoid blocking.
+
+use core::convert::Infallible;
+
+use embedded_io::{ErrorType, Read, ReadReady, Write, WriteReady};
+
+use crate::{Overflow, RingBuf, RingLock, Storage};
+
+impl<S: Storage<Item = u8>, L: RingLock> RingBuf<S, L> {
+    /// Read at least one byte into `buf`, spinning while the buffer is empty.
+    /// Waiting isn't a failed read, so it doesn't count toward `empty_reads`.
+    fn blocking_read(&self, buf: &mut [u8]) -> usize {
+        if buf.is_empty() {
+            return 0;
+        }
+        loop {
+            while self.empty() {
+                self.lock.relax();
+            }
+            // another reader may still beat us to the data
+            match self.read_slice_uncounted(buf) {
+                0 => self.lock.relax(),
+                count => return count,
+            }
+        }
+    }
+
+    /// Write at least one byte from `buf`, spinning while the buffer is full.
+    /// Bytes that wait for room are written later, so they count as neither rejected nor dropped.
+    fn blocking_write(&self, buf: &[u8]) -> usize {
+        if buf.is_empty() {
+            return 0;
+        }
+        // a longer write would keep only its tail under `OverwriteOldest`,
+        // but the count we return has to describe a prefix of `buf`
+        let buf = &buf[..buf.len().min(self.capacity)];
+        loop {
+            while !self.accepts_write() {
+                self.lock.relax();
+            }
+            match self.push_slice_uncounted(buf) {
+                0 => self.lock.relax(),
+                count => return count,
+            }
+        }
+    }
+
+    /// Would a write accept at least one byte right now?
+    fn accepts_write(&self) -> bool {
+        self.overflow == Overflow::OverwriteOldest || !self.full()
+    }
+}
+
+
+impl<S: Storage<Item = u8>, L: RingLock> ErrorType for &RingBuf<S, L> {
+    type Error = Infallible;
+}
+
+
+impl<S: Storage<Item = u8>, L: RingLock> Read for &RingBuf<S, L> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        Ok(self.blocking_read(buf))
+    }
+}
+
+
+impl<S: Storage<Item = u8>, L: RingLock> Write for &RingBuf<S, L> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        Ok(self.blocking_write(buf))
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+
+impl<S: Storage<Item = u8>, L: RingLock> ReadReady for &RingBuf<S, L> {
+    fn read_ready(&mut self) -> Result<bool, Self::Error> {
+        Ok(!self.empty())
+    }
+}
+
+
+impl<S: Storage<Item = u8>, L: RingLock> WriteReady for &RingBuf<S, L> {
+    fn write_ready(&mut self) -> Result<bool, Self::Error> {
+        Ok(self.accepts_write())
+    }
+}
+
+#[cfg(feature = "embedded-io-async")]
+mod asynch {
+    use embedded_io_async::{Read, Write};
+
+    use crate::{RingBuf, RingLock, Storage};
+
+
+    impl<S: Storage<Item = u8>, L: RingLock> Read for &RingBuf<S, L> {
+        async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+            Ok(self.read_slice_async(buf).await)
+        }
+    }
+
+
+    impl<S: Storage<Item = u8>, L: RingLock> Write for &RingBuf<S, L> {
+        async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+            Ok(self.push_slice_async(buf).await)
+        }
+    }
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use super::*;
+    use crate::Ringu;
+    use std::thread;
+
+    #[test]
+    fn blocking_read_write_threads() {
+        const COUNT: usize = if cfg!(miri) { 100 } else { 5000 };
+        // yield rather than busy-spin, in case the test threads share a core
+        static BFFL: Ringu<u8, 16> = Ringu::new_with_spin(thread::yield_now);
+
+        thread::scope(|scope| {
+            scope.spawn(|| {
+                let src: Vec<u8> = (0..COUNT).map(|i| i as u8).collect();
+                (&BFFL).write_all(&src).unwrap();
+            });
+
+            let mut dst = vec![0u8; COUNT];
+            (&BFFL).read_exact(&mut dst).unwrap();
+            for (i, val) in dst.iter().enumerate() {
+                assert_eq!(*val, i as u8);
+            }
+        });
+    }
+
+    #[test]
+    fn ready_flags() {
+        let bffl = Ringu::<u8, 2>::new();
+        assert!(!(&bffl).read_ready().unwrap());
+        assert!((&bffl).write_ready().unwrap());
+        assert_eq!((&bffl).write(&[1, 2, 3]).unwrap(), 2);
+        assert!((&bffl).read_ready().unwrap());
+        assert!(!(&bffl).write_ready().unwrap());
+
+        let overwrite = Ringu::<u8, 2>::new_with_overflow(Overflow::OverwriteOldest);
+        (&overwrite).write_all(&[1, 2, 3]).unwrap();
+        assert!((&overwrite).write_ready().unwrap());
+        assert_eq!(overwrite.drain().collect::<Vec<_>>(), [2, 3]);
+    }
+
+    #[test]
+    fn waiting_is_not_dropping() {
+        static BFFL: Ringu<u8, 4> = Ringu::new_with_lock(
+            crate::SpinLock::new_with_spin(thread::yield_now), Overflow::DropNewest);
+
+        thread::scope(|scope| {
+            scope.spawn(|| (&BFFL).write_all(&[1, 2, 3, 4, 5, 6, 7, 8]).unwrap());
+            let mut dst = [0u8; 8];
+            (&BFFL).read_exact(&mut dst).unwrap();
+            assert_eq!(dst, [1, 2, 3, 4, 5, 6, 7, 8]);
+        });
+        assert_eq!(BFFL.dropped(), 0);
+
+        // a partial write leaves the rest to the caller, so none of it is dropped either
+        let bffl = Ringu::<u8, 4>::new_with_overflow(Overflow::DropNewest);
+        bffl.push_slice(&[0, 0]);
+        assert_eq!((&bffl).write(&[1, 2, 3, 4]).unwrap(), 2);
+        assert_eq!(bffl.dropped(), 0);
+        #[cfg(feature = "stats")]
+        {
+            let stats = BFFL.stats();
+            assert_eq!(stats.rejected, 0);
+            assert_eq!(stats.empty_reads, 0);
+        }
+    }
+
+    #[cfg(feature = "embedded-io-async")]
+    #[test]
+    fn async_read_write() {
+        use crate::waker::tests::block_on;
+
+        let bffl = Ringu::<u8, 8>::new();
+        thread::scope(|scope| {
+            scope.spawn(|| block_on(async {
+                let src: Vec<u8> = (0..100).collect();
+                embedded_io_async::Write::write_all(&mut &bffl, &src).await.unwrap();
+            }));
+            let mut dst = [0u8; 100];
+            block_on(embedded_io_async::Read::read_exact(&mut &bffl, &mut dst)).unwrap();
+            assert!(dst.iter().enumerate().all(|(i, val)| *val == i as u8));
+        });
+    }
+}
@@ -0,0 +1,60 @@
+/*
+Copyright (c) 2022 Todd Stellanova
+LICENSE: BSD3 (see LICENSE file)
+*/
+
+//! Error types returned by [`Ringu`](crate::Ringu) operations.
+
+use core::fmt;
+
+/// Why an operation on a [`Ringu`](crate::Ringu) could not complete
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// There is no room for more data
+    Full,
+    /// There is no data to read
+    Empty,
+    /// The lock is held by someone else, and the operation doesn't wait
+    WouldBlock,
+    /// The buffer's indices are inconsistent
+    Corrupted,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let msg = match self {
+            Error::Full => "ring buffer is full",
+            Error::Empty => "ring buffer is empty",
+            Error::WouldBlock => "ring buffer is locked",
+            Error::Corrupted => "ring buffer indices are corrupted",
+        };
+        f.write_str(msg)
+    }
+}
+
+impl core::error::Error for Error {}
+
+/// A push was refused because the buffer is full; holds the element that was not pushed
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Full<T>(pub T);
+
+impl<T> Full<T> {
+    /// Take back the element that was not pushed
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> fmt::Display for Full<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&Error::Full, f)
+    }
+}
+
+impl<T: fmt::Debug> core::error::Error for Full<T> {}
+
+impl<T> From<Full<T>> for Error {
+    fn from(_: Full<T>) -> Self {
+        Error::Full
+    }
+}
@@ -0,0 +1,95 @@
+/*
+Copyright (c) 2022 Todd Stellanova
+LICENSE: BSD3 (see LICENSE file)
+*/
+
+//! [`core::fmt::Write`] for byte buffers, so text can be formatted straight
+//! into a `Ringu` with `write!` and no intermediate buffer.
+//!
+//! What happens when the text doesn't fit follows the buffer's [`Overflow`] policy:
+//! - `Reject`: a piece of text that doesn't fit is not written at all
+//!   (so no character is cut in half), and `fmt::Error` is returned
+//! - `DropNewest`: the text is silently truncated, and the rest counted as dropped
+//! - `OverwriteOldest`: the oldest bytes are overwritten, keeping the latest text
+//!
+//! Each `write_str` is pushed under a single lock, but a `write!` with several
+//! arguments may interleave with text from other writers.
+
+use core::fmt;
+
+use crate::{Overflow, RingBuf, RingLock, Storage};
+
+impl<S: Storage<Item = u8>, L: RingLock> RingBuf<S, L> {
+    fn push_str(&self, s: &str) -> fmt::Result {
+        if self.overflow != Overflow::Reject {
+            self.push_slice(s.as_bytes());
+            return Ok(());
+        }
+        // check for room under the same lock as the push, so it's all or nothing
+        self.lock_me();
+        if self.vacant() < s.len() {
+            self.unlock_me();
+            self.reject(s.len());
+            return Err(fmt::Error);
+        }
+        self.push_slice_locked(s.as_bytes());
+        Ok(())
+    }
+}
+
+impl<S: Storage<Item = u8>, L: RingLock> fmt::Write for RingBuf<S, L> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.push_str(s)
+    }
+}
+
+impl<S: Storage<Item = u8>, L: RingLock> fmt::Write for &RingBuf<S, L> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.push_str(s)
+    }
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use super::*;
+    use crate::Ringu;
+    use core::fmt::Write;
+
+    fn drain(bffl: &Ringu<u8, 16>) -> String {
+        let mut dst = [0u8; 16];
+        let nread = bffl.read_slice(&mut dst);
+        String::from_utf8(dst[..nread].to_vec()).unwrap()
+    }
+
+    #[test]
+    fn write_formatted() {
+        static LOG: Ringu<u8, 16> = Ringu::new();
+        write!(&LOG, "temp={}", 21.5).unwrap();
+        assert_eq!(drain(&LOG), "temp=21.5");
+    }
+
+    #[test]
+    fn full_behavior_follows_overflow_policy() {
+        let mut reject = Ringu::<u8, 16>::new();
+        assert!(write!(reject, "0123456789abcdefXYZ").is_err());
+        assert!(reject.empty());
+        // pieces that fit are kept, up to the one that doesn't
+        let tail = "abcdefXYZ";
+        assert!(write!(reject, "0123456789{tail}").is_err());
+        assert_eq!(drain(&reject), "0123456789");
+        // one byte short of a two-byte character, which must not be split
+        write!(reject, "0123456789abcde").unwrap();
+        assert!(write!(reject, "é").is_err());
+        assert_eq!(drain(&reject), "0123456789abcde");
+
+        let mut truncate = Ringu::<u8, 16>::new_with_overflow(Overflow::DropNewest);
+        assert!(write!(truncate, "0123456789abcdefXYZ").is_ok());
+        assert_eq!(truncate.dropped(), 3);
+        assert_eq!(drain(&truncate), "0123456789abcdef");
+
+        let mut overwrite = Ringu::<u8, 16>::new_with_overflow(Overflow::OverwriteOldest);
+        let tail = "XYZ";
+        assert!(write!(overwrite, "0123456789abcdef{tail}").is_ok());
+        assert_eq!(drain(&overwrite), "3456789abcdefXYZ");
+    }
+}
@@ -0,0 +1,156 @@
+/*
+Copyright (c) 2022 Todd Stellanova
+LICENSE: BSD3 (see LICENSE file)
+*/
+
+//! Zero-copy access to the storage of a split byte [`Ringu`].
+//!
+//! A grant borrows its half of the split buffer mutably, so while a grant is
+//! outstanding nothing else can move that half's index. The other half keeps
+//! running concurrently since it never touches the granted region.
+
+use crate::sync::Ordering;
+
+use crate::{Consumer, Producer, RingBuf, RingLock, SpinLock, Storage};
+
+/// A contiguous, writable region of the buffer reserved by [`Producer::grant_write`].
+/// Nothing written here is visible to the consumer until [`WriteGrant::commit`];
+/// dropping the grant without committing abandons it.
+pub struct WriteGrant<'a, S: Storage, L: RingLock = SpinLock> {
+    ring: &'a RingBuf<S, L>,
+    /// The unbounded write index where the region begins
+    start: usize,
+    len: usize,
+}
+
+/// All bytes that were readable when [`Consumer::grant_read`] was called,
+/// viewed in place. Only the bytes passed to [`ReadGrant::release`] are consumed;
+/// dropping the grant without releasing leaves everything for the next grant.
+pub struct ReadGrant<'a, S: Storage, L: RingLock = SpinLock> {
+    ring: &'a RingBuf<S, L>,
+    /// The unbounded read index where the readable region begins
+    start: usize,
+    len: usize,
+}
+
+impl<S: Storage<Item = u8>, L: RingLock> Producer<'_, S, L> {
+    /// Reserve up to `max` vacant bytes for writing in place.
+    /// The region stops at the wrap point, so it may be shorter than the total
+    /// vacant space; it is empty when the buffer is full.
+    pub fn grant_write(&mut self, max: usize) -> WriteGrant<'_, S, L> {
+        let ring = self.ring();
+        let start = ring.write_idx.load(Ordering::Relaxed);
+        let len = max.min(self.vacant()).min(ring.to_wrap(start));
+        WriteGrant { ring, start, len }
+    }
+}
+
+impl<S: Storage<Item = u8>, L: RingLock> WriteGrant<'_, S, L> {
+    /// The reserved region, to be filled by the caller (or a DMA peripheral)
+    pub fn buf(&mut self) -> &mut [u8] {
+        self.ring.cells.write(self.start, self.len);
+        // Safety: the region is vacant, contiguous, and only this grant's producer
+        // writes vacant slots; byte slots are always initialized (see `Ringu::buf`)
+        unsafe { core::slice::from_raw_parts_mut(self.ring.slot(self.start), self.len) }
+    }
+
+    /// Publish the first `used` bytes of the region to the consumer.
+    /// `used` is clamped to the size of the grant.
+    pub fn commit(self, used: usize) {
+        let used = used.min(self.len);
+        let write = self.start.wrapping_add(used);
+        self.ring.write_idx.store(write, Ordering::Release);
+        self.ring.stats.wrote(used, write.wrapping_sub(self.ring.read_idx.load(Ordering::Acquire)));
+    }
+}
+
+impl<S: Storage<Item = u8>, L: RingLock> Consumer<'_, S, L> {
+    /// Borrow every byte currently readable, without consuming any of it
+    pub fn grant_read(&mut self) -> ReadGrant<'_, S, L> {
+        let ring = self.ring();
+        let start = ring.read_idx.load(Ordering::Relaxed);
+        let len = self.available();
+        ReadGrant { ring, start, len }
+    }
+}
+
+impl<S: Storage<Item = u8>, L: RingLock> ReadGrant<'_, S, L> {
+    /// The readable bytes as two slices: up to the wrap point, then from the start of
+    /// the buffer. The second slice is empty unless the readable region wraps.
+    pub fn bufs(&self) -> (&[u8], &[u8]) {
+        let first = self.len.min(self.ring.to_wrap(self.start));
+        self.ring.cells.read(self.start, self.len);
+        // Safety: the region was published by the producer, which won't write it
+        // again until this consumer releases it
+        unsafe {
+            (
+                core::slice::from_raw_parts(self.ring.slot(self.start), first),
+                core::slice::from_raw_parts(self.ring.slot(self.start.wrapping_add(first)), self.len - first),
+            )
+        }
+    }
+
+    /// Consume the first `used` bytes of the grant, making room for the producer.
+    /// `used` is clamped to the size of the grant.
+    pub fn release(self, used: usize) {
+        let used = used.min(self.len);
+        self.ring.read_idx.store(self.start.wrapping_add(used), Ordering::Release);
+        self.ring.stats.read(used);
+    }
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use crate::Ringu;
+
+    #[test]
+    fn write_grant_commit_and_abandon() {
+        let mut bffl = Ringu::<u8, 8>::default();
+        let (mut producer, mut consumer) = bffl.split();
+
+        let mut grant = producer.grant_write(5);
+        grant.buf().copy_from_slice(&[1, 2, 3, 4, 5]);
+        grant.commit(3);
+        assert_eq!(consumer.available(), 3);
+
+        // an abandoned grant leaves the buffer untouched
+        producer.grant_write(4).buf()[0] = 99;
+        assert_eq!(consumer.available(), 3);
+
+        let mut dst = [0u8; 8];
+        assert_eq!(consumer.read_slice(&mut dst), 3);
+        assert_eq!(dst[..3], [1, 2, 3]);
+
+        // the next grant is limited by the wrap point, not the vacant space
+        let mut grant = producer.grant_write(8);
+        assert_eq!(grant.buf().len(), 5);
+        grant.buf().fill(7);
+        grant.commit(8);
+        assert_eq!(producer.grant_write(8).len, 3);
+        assert_eq!(consumer.read_slice(&mut dst), 5);
+        assert_eq!(dst[..5], [7; 5]);
+    }
+
+    #[test]
+    fn read_grant_partial_release() {
+        let mut bffl = Ringu::<u8, 8>::default();
+        let (mut producer, mut consumer) = bffl.split();
+        assert_eq!(consumer.grant_read().bufs(), (&[][..], &[][..]));
+
+        // offset the indices so that the readable region wraps
+        assert_eq!(producer.push_slice(&[0; 6]), 6);
+        assert_eq!(consumer.read_slice(&mut [0; 6]), 6);
+        assert_eq!(producer.push_slice(&[1, 2, 3, 4, 5]), 5);
+
+        let grant = consumer.grant_read();
+        assert_eq!(grant.bufs(), (&[1, 2][..], &[3, 4, 5][..]));
+        grant.release(3);
+
+        // unreleased bytes are still there for the next grant
+        let grant = consumer.grant_read();
+        assert_eq!(grant.bufs(), (&[4, 5][..], &[][..]));
+        grant.release(0);
+        assert_eq!(consumer.available(), 2);
+        assert_eq!(producer.vacant(), 6);
+    }
+}
@@ -0,0 +1,96 @@
+/*
+Copyright (c) 2022 Todd Stellanova
+LICENSE: BSD3 (see LICENSE file)
+*/
+
+//! Ring buffers sized at runtime, with their storage on the heap (the `alloc` feature).
+
+use crate::lock::unlocked;
+use crate::{HeapSlots, Overflow, RingBuf, RingLock, SpinLock};
+
+/// A ring buffer of elements of type `T` whose capacity is chosen at construction.
+/// It behaves exactly like a [`Ringu`](crate::Ringu), locking included.
+///
+/// ```
+/// let bffl = ringu::HeapRingu::<u8>::with_capacity(1000);
+/// assert_eq!(bffl.capacity(), 1024);
+/// ```
+pub type HeapRingu<T, L = SpinLock> = RingBuf<HeapSlots<T>, L>;
+
+impl<T> HeapRingu<T> {
+    /// Create an empty buffer with room for at least `capacity` elements,
+    /// rounded up to a power of two.
+    ///
+    /// # Panics
+    ///
+    /// If the rounded-up capacity doesn't fit in a `usize`.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self::with_capacity_and_lock(capacity, unlocked(), Overflow::Reject)
+    }
+}
+
+impl<T, L: RingLock> HeapRingu<T, L> {
+    /// Create an empty buffer with room for at least `capacity` elements,
+    /// rounded up to a power of two, guarded by `lock` and with the given overflow policy.
+    ///
+    /// # Panics
+    ///
+    /// If the rounded-up capacity doesn't fit in a `usize`.
+    pub fn with_capacity_and_lock(capacity: usize, lock: L, overflow: Overflow) -> Self {
+        let capacity = capacity.checked_next_power_of_two().expect("HeapRingu capacity overflows usize");
+        // zeroed, so that byte buffers can hand out write grants
+        Self::with_storage(HeapSlots::zeroed(capacity), capacity, lock, overflow)
+    }
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn capacity_rounds_up() {
+        assert_eq!(HeapRingu::<u8>::with_capacity(0).capacity(), 1);
+        assert_eq!(HeapRingu::<u8>::with_capacity(5).capacity(), 8);
+        assert_eq!(HeapRingu::<u8>::with_capacity(64).capacity(), 64);
+    }
+
+    #[test]
+    #[should_panic(expected = "overflows")]
+    fn capacity_overflow_panics() {
+        let _ = HeapRingu::<u64>::with_capacity(usize::MAX / 2 + 2);
+    }
+
+    #[test]
+    fn wraps_and_drops_like_ringu() {
+        let tracker = Arc::new(());
+        let bffl = HeapRingu::<Arc<()>, SpinLock>::with_capacity_and_lock(3, unlocked(), Overflow::OverwriteOldest);
+        for _ in 0..6 {
+            assert!(bffl.try_push(tracker.clone()).is_ok());
+        }
+        assert_eq!(bffl.available(), 4);
+        assert_eq!(bffl.dropped(), 2);
+        assert_eq!(Arc::strong_count(&tracker), 5);
+        drop(bffl);
+        assert_eq!(Arc::strong_count(&tracker), 1);
+    }
+
+    #[test]
+    fn odd_capacity_wraps_around() {
+        let bffl = HeapRingu::<u16>::with_capacity(5);
+        let mut dst = [0; 8];
+        // start part way in, so every lap crosses the end of the 8 slots
+        assert_eq!(bffl.push_slice(&[0; 3]), 3);
+        assert_eq!(bffl.read_slice(&mut dst[..3]), 3);
+        for lap in 0..5u16 {
+            let src: [u16; 6] = core::array::from_fn(|i| lap * 10 + i as u16);
+            assert_eq!(bffl.push_slice(&src), 6);
+            assert_eq!(bffl.push_slice(&src), 2);
+            assert!(bffl.full());
+            assert_eq!(bffl.read_slice(&mut dst), 8);
+            assert_eq!(&dst[..6], &src);
+            assert_eq!(&dst[6..], &src[..2]);
+        }
+        assert!(bffl.empty());
+    }
+}
@@ -0,0 +1,193 @@
+/*
+Copyright (c) 2022 Todd Stellanova
+LICENSE: BSD3 (see LICENSE file)
+*/
+
+//! Iterator support for [`Ringu`].
+
+use core::iter::FusedIterator;
+use crate::lock::unlocked;
+use crate::sync::Ordering;
+use crate::storage::InlineSlots;
+#[cfg(feature = "alloc")]
+use crate::storage::HeapSlots;
+use crate::{LockInit, Overflow, RingBuf, RingLock, Ringu, SpinLock, Storage};
+#[cfg(feature = "alloc")]
+use alloc::{boxed::Box, vec::Vec};
+
+/// Pops the elements that were available when [`Ringu::drain`] was called.
+/// Elements pushed after that are left for later, so a busy writer
+/// can't keep the iterator going forever.
+pub struct Drain<'a, S: Storage, L: RingLock = SpinLock> {
+    ring: &'a RingBuf<S, L>,
+    remaining: usize,
+}
+
+/// Borrows each element of the readable region in turn, oldest first.
+/// Created by [`Ringu::iter`].
+pub struct Iter<'a, S: Storage, L: RingLock = SpinLock> {
+    ring: &'a RingBuf<S, L>,
+    /// The unbounded index of the next element to yield
+    idx: usize,
+    remaining: usize,
+}
+
+impl<T, S: Storage<Item = T>, L: RingLock> RingBuf<S, L> {
+    /// Remove and iterate over every element currently in the buffer
+    pub fn drain(&self) -> Drain<'_, S, L> {
+        Drain { ring: self, remaining: self.available() }
+    }
+
+    /// Iterate over the readable elements without consuming them.
+    /// This borrows the buffer exclusively, since another reader or an
+    /// overwriting writer could otherwise discard an element while we borrow it.
+    pub fn iter(&mut self) -> Iter<'_, S, L> {
+        let idx = self.read_idx.load(Ordering::Relaxed);
+        let remaining = self.available();
+        Iter { ring: self, idx, remaining }
+    }
+}
+
+impl<T, S: Storage<Item = T>, L: RingLock> Iterator for Drain<'_, S, L> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+        let item = self.ring.try_pop();
+        if item.is_none() {
+            // another reader got there first
+            self.remaining = 0;
+        }
+        item
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.remaining))
+    }
+}
+
+impl<T, S: Storage<Item = T>, L: RingLock> FusedIterator for Drain<'_, S, L> {}
+
+impl<'a, T: 'a, S: Storage<Item = T>, L: RingLock> Iterator for Iter<'a, S, L> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.ring.cells.read(self.idx, 1);
+        // Safety: slots in the readable region are initialized, and the
+        // buffer is borrowed exclusively for as long as this iterator lives
+        let item = unsafe { &*self.ring.slot(self.idx) };
+        self.idx = self.idx.wrapping_add(1);
+        self.remaining -= 1;
+        Some(item)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'a, T: 'a, S: Storage<Item = T>, L: RingLock> ExactSizeIterator for Iter<'a, S, L> {}
+
+impl<'a, T: 'a, S: Storage<Item = T>, L: RingLock> FusedIterator for Iter<'a, S, L> {}
+
+impl<T, S: Storage<Item = T>, L: RingLock> Extend<T> for RingBuf<S, L> {
+    /// Push elements until the buffer is full (never, under `OverwriteOldest`).
+    /// Whatever remains of the iterator is not consumed.
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        let mut iter = iter.into_iter();
+        // we have the buffer to ourselves, so room seen here is still there for the push
+        while self.overflow == Overflow::OverwriteOldest || !self.full() {
+            let Some(item) = iter.next() else { break };
+            if self.try_push(item).is_err() {
+                break;
+            }
+        }
+    }
+}
+
+// A blanket `Extend<&T>` would overlap `Extend<T>` for storage of references,
+// so each storage this crate provides gets its own.
+macro_rules! extend_copied {
+    ($([$($param:tt)*] $storage:ty => $item:ty;)*) => {$(
+        impl<'a, $($param)* L: RingLock> Extend<&'a $item> for RingBuf<$storage, L> {
+            fn extend<I: IntoIterator<Item = &'a $item>>(&mut self, iter: I) {
+                self.extend(iter.into_iter().copied());
+            }
+        }
+    )*};
+}
+
+extend_copied! {
+    [T: Copy + 'a, const N: usize,] InlineSlots<T, N> => T;
+    [const N: usize,] [u8; N] => u8;
+    [] &mut [u8] => u8;
+}
+
+#[cfg(feature = "alloc")]
+extend_copied! {
+    [T: Copy + 'a,] HeapSlots<T> => T;
+    [] Box<[u8]> => u8;
+    [] Vec<u8> => u8;
+}
+
+impl<T, const N: usize, L: LockInit> FromIterator<T> for Ringu<T, N, L> {
+    /// Collect up to the first N elements of the iterator
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut ring = Self::new_with_lock(unlocked(), Overflow::Reject);
+        ring.extend(iter);
+        ring
+    }
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use super::*;
+    use core::sync::atomic::Ordering;
+
+    #[test]
+    fn drain_stops_at_snapshot() {
+        let bffl: Ringu<u8, 8> = (1..=5).collect();
+        let mut drain = bffl.drain();
+        assert_eq!(drain.next(), Some(1));
+        // pushed after the snapshot, so left for later
+        bffl.push_one(6);
+        assert_eq!(drain.collect::<Vec<_>>(), [2, 3, 4, 5]);
+        assert_eq!(bffl.drain().collect::<Vec<_>>(), [6]);
+        assert!(bffl.empty());
+    }
+
+    #[test]
+    fn extend_until_full() {
+        let mut bffl = Ringu::<u8, 4>::new();
+        let mut src = 0..10;
+        bffl.extend(&mut src);
+        // the rest of the iterator is left alone
+        assert_eq!(src.next(), Some(4));
+        assert_eq!(bffl.drain().collect::<Vec<_>>(), [0, 1, 2, 3]);
+
+        bffl.extend(&[7, 8]);
+        assert_eq!(bffl.available(), 2);
+    }
+
+    #[test]
+    fn iter_does_not_consume() {
+        let mut bffl = Ringu::<u8, 4>::new();
+        bffl.push_slice(&[0, 0, 0]);
+        bffl.read_slice(&mut [0; 3]);
+        bffl.push_slice(&[1, 2, 3, 4]);
+        let start = bffl.read_idx.load(Ordering::Relaxed);
+
+        // the readable region wraps around the end of the array
+        let iter = bffl.iter();
+        assert_eq!(iter.len(), 4);
+        assert_eq!(iter.copied().collect::<Vec<_>>(), [1, 2, 3, 4]);
+        assert_eq!(bffl.read_idx.load(Ordering::Relaxed), start);
+        assert_eq!(bffl.available(), 4);
+    }
+}
@@ -5,90 +5,289 @@ LICENSE: BSD3 (see LICENSE file)
 
 #![cfg_attr(not(test), no_std)]
 
-use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering };
+#[cfg(feature = "alloc")]
+extern crate alloc;
+#[cfg(any(feature = "std", loom))]
+extern crate std;
+
+use core::cell::UnsafeCell;
+
+use stats::Counters;
+use sync::{const_fn, AtomicUsize, Ordering, SlotCells};
+use waker::AtomicWaker;
+
+#[cfg(feature = "embedded-io")]
+mod eio;
+mod borrowed;
+mod buffer;
+mod fmt;
+mod error;
+mod grant;
+#[cfg(feature = "alloc")]
+mod heap;
+mod iter;
+mod lock;
+mod mpmc;
+mod split;
+mod stats;
+#[cfg(feature = "std")]
+mod stdio;
+mod storage;
+mod sync;
+mod waker;
+pub use borrowed::SliceRingu;
+pub use buffer::RingBuffer;
+pub use error::{Error, Full};
+pub use grant::{ReadGrant, WriteGrant};
+#[cfg(feature = "alloc")]
+pub use heap::HeapRingu;
+pub use iter::{Drain, Iter};
+#[cfg(feature = "critical-section")]
+pub use lock::CriticalSectionLock;
+#[cfg(feature = "std")]
+pub use lock::MutexLock;
+pub use lock::{LockInit, NullLock, RingLock, SpinFunc, SpinLock};
+pub use mpmc::MpmcRingu;
+pub use split::{Consumer, Producer};
+#[cfg(feature = "stats")]
+pub use stats::Stats;
+pub use storage::{InlineSlots, Storage};
+#[cfg(feature = "alloc")]
+pub use storage::HeapSlots;
 
 // pub const BUF_LEN: usize = 256;
 
-pub type SpinFunc = fn() ;
+/// What a push does when the buffer is already full
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Overflow {
+    /// Refuse the new data (the default)
+    Reject,
+    /// Discard the oldest data to make room, keeping the most recent history
+    OverwriteOldest,
+    /// Refuse the new data and count it as dropped
+    DropNewest,
+}
+
+/// A ring buffer of up to `N` elements of type `T`, stored inline.
+/// `Ringu<u8, N>` additionally provides the byte-oriented `push_one` / `read_one` API.
+///
+/// `N` must be a non-zero power of two; anything else fails to compile:
+///
+/// ```compile_fail
+/// static RX: ringu::Ringu<u8, 1000> = ringu::Ringu::new();
+/// ```
+///
+/// `L` is the [`RingLock`] guarding every update, a [`SpinLock`] unless chosen otherwise.
+pub type Ringu<T, const N: usize, L = SpinLock> = RingBuf<InlineSlots<T, N>, L>;
+
+/// A ring buffer over the element slots of storage `S`, guarded by lock `L`.
+/// Usually named through one of its aliases: [`Ringu`], [`SliceRingu`] or (with the `alloc` feature) `HeapRingu`.
+pub struct RingBuf<S: Storage, L: RingLock = SpinLock> {
+    /// The actual buffer.
+    /// Only accessed while holding `lock`, which is what makes sharing `&Ringu` sound.
+    /// Slots between `read_idx` and `write_idx` hold live elements.
+    /// The storage starts out zeroed, so for `u8` every slot is always a valid byte,
+    /// which is what lets the producer hand out write grants as `&mut [u8]`.
+    buf: UnsafeCell<S>,
 
-pub struct Ringu<const N: usize> {
-    /// The actual buffer
-    buf: [u8; N],
+    /// Loom's stand-in for the slots of `buf` (empty unless built for loom)
+    cells: SlotCells,
 
-    /// The index at which the next byte should be read from the buffer
+    /// How many elements `buf` holds, a non-zero power of two
+    capacity: usize,
+
+    /// The index at which the next element should be read from the buffer
     /// This grows unbounded until it wraps, and is only masked into
     /// the inner buffer range when we access the array.
     read_idx: AtomicUsize,
 
-    /// The index at which the next byte should be written to the buffer
+    /// The index at which the next element should be written to the buffer
     /// This grows unbounded until it wraps, and is only masked into
     /// the inner buffer range when we access the array.
     write_idx: AtomicUsize,
 
     /// A mutability lock
-    mut_lock: AtomicBool,
+    lock: L,
+
+    /// Usage counters (no-ops without the `stats` feature)
+    stats: Counters,
 
-    /// Optional user-overridden spin lock function
-    spin_func: SpinFunc,
+    /// What to do when pushing into a full buffer
+    overflow: Overflow,
 
-    /// tracking bytes read
-    read_count: AtomicUsize,
+    /// Elements discarded by the overflow policy
+    dropped: AtomicUsize,
+
+    /// The task waiting for data to read, if any
+    readable_waker: AtomicWaker,
+
+    /// The task waiting for room to write, if any
+    writable_waker: AtomicWaker,
 }
 
-impl<const N: usize> Ringu<N> {
-    pub fn default() -> Self {
-        Self {
-            buf: [0; N],
-            read_idx: AtomicUsize::new(0),
-            write_idx: AtomicUsize::new(0),
-            mut_lock: AtomicBool::new(false),
-            spin_func: Self::spinlock,
-            read_count: AtomicUsize::new(0),
+// Safety: every access to `buf` happens while holding the lock,
+// and the indices are atomics, so a shared `&Ringu` may be used from any thread.
+// Elements move between threads, hence `T: Send`; the lock must be shareable too.
+// Like a `Mutex`, the storage is used from one thread at a time, so it need only be `Send`.
+unsafe impl<S: Storage + Send, L: RingLock + Sync> Sync for RingBuf<S, L> where S::Item: Send {}
+
+impl<T, const N: usize> Ringu<T, N> {
+    const_fn! {
+        /// Create an empty buffer.
+        /// This is a `const fn`, so a `Ringu` can be placed in a plain `static`.
+        pub fn new() -> Self {
+            Self::new_with_lock(SpinLock::new_with_spin(sync::spin_loop), Overflow::Reject)
         }
     }
 
-    /// Provide a custom spin function that will be called when we're trying to lock this struct
-    pub fn new_with_spin(spin: SpinFunc) -> Self {
-        Self {
-            buf: [0; N],
-            read_idx: AtomicUsize::new(0),
-            write_idx: AtomicUsize::new(0),
-            mut_lock: AtomicBool::new(false),
-            spin_func: spin,
-            read_count: AtomicUsize::new(0),
+    const_fn! {
+        /// Provide a custom spin function that will be called when we're trying to lock this struct,
+        /// and while blocking on an empty or full buffer
+        pub fn new_with_spin(spin: SpinFunc) -> Self {
+            Self::new_with_lock(SpinLock::new_with_spin(spin), Overflow::Reject)
         }
     }
 
-    fn lock_me(&mut self) {
-        while self.mut_lock.compare_and_swap(false, true, Ordering::Acquire) != false {
-            while self.mut_lock.load(Ordering::Relaxed) {
-                (self.spin_func)();
+    const_fn! {
+        /// Choose what happens when pushing into a full buffer.
+        /// The split [`Producer`] cannot move the read index, so it always rejects when full.
+        pub fn new_with_overflow(overflow: Overflow) -> Self {
+            Self::new_with_lock(SpinLock::new_with_spin(sync::spin_loop), overflow)
+        }
+    }
+}
+
+impl<T, const N: usize, L: RingLock> Ringu<T, N, L> {
+    /// Indices are masked into the storage with `N - 1`,
+    /// which only works for a non-zero power of two.
+    const CAPACITY_OK: () = assert!(N.is_power_of_two(), "Ringu capacity N must be a non-zero power of two");
+
+    const_fn! {
+        /// Create an empty buffer guarded by `lock`, with the given overflow policy.
+        /// Pass `L::INIT` for a lock that needs no configuration:
+        ///
+        /// ```
+        /// use ringu::{LockInit, NullLock, Overflow, Ringu};
+        /// let bffl = Ringu::<u8, 16, NullLock>::new_with_lock(NullLock::INIT, Overflow::Reject);
+        /// assert_eq!(bffl.push_one(7), 1);
+        /// ```
+        pub fn new_with_lock(lock: L, overflow: Overflow) -> Self {
+            // reject a bad capacity at compile time, when the constructor is instantiated
+            let () = Self::CAPACITY_OK;
+            // zeroed, so that byte buffers can hand out write grants
+            Self::with_storage(InlineSlots::zeroed(), N, lock, overflow)
+        }
+    }
+}
+
+impl<T, S: Storage<Item = T>, L: RingLock> RingBuf<S, L> {
+    const_fn! {
+        /// Wrap `buf`, whose `capacity` must be a non-zero power of two,
+        /// and whose byte slots (if any) must be initialized
+        pub(crate) fn with_storage(buf: S, capacity: usize, lock: L, overflow: Overflow) -> Self {
+            Self {
+                buf: UnsafeCell::new(buf),
+                cells: SlotCells::new(capacity),
+                capacity,
+                read_idx: AtomicUsize::new(0),
+                write_idx: AtomicUsize::new(0),
+                lock,
+                stats: Counters::new(),
+                overflow,
+                dropped: AtomicUsize::new(0),
+                readable_waker: AtomicWaker::new(),
+                writable_waker: AtomicWaker::new(),
             }
         }
     }
 
-    fn unlock_me(&mut self) {
-        self.mut_lock.compare_and_swap(true, false, Ordering::Acquire);
+    fn lock_me(&self) {
+        self.stats.spun(self.lock.lock());
     }
 
-    fn spinlock() {
-        core::hint::spin_loop();
+    fn unlock_me(&self) {
+        // Safety: only called after `lock_me` or a successful `try_lock_me`
+        unsafe { self.lock.unlock() }
+    }
+
+    fn try_lock_me(&self) -> bool {
+        self.lock.try_lock()
+    }
+
+    /// Raw pointer to the buffer slot that an unbounded index maps to
+    fn slot(&self, idx: usize) -> *mut T {
+        // Safety: `buf` is live, and masking keeps the offset within the storage
+        unsafe { S::slots(self.buf.get()).add(idx & (self.capacity - 1)) }
+    }
+
+    /// How many slots from the unbounded index `idx` up to the wrap point
+    fn to_wrap(&self, idx: usize) -> usize {
+        self.capacity - (idx & (self.capacity - 1))
+    }
+
+    /// Copy `src` into the buffer starting at the unbounded index `idx`,
+    /// in at most two chunks across the wrap point.
+    ///
+    /// Safety: the caller must have exclusive access to the `src.len()` slots starting at `idx`
+    unsafe fn copy_in(&self, idx: usize, src: &[T]) where T: Copy {
+        self.cells.write(idx, src.len());
+        let first = src.len().min(self.to_wrap(idx));
+        core::ptr::copy_nonoverlapping(src.as_ptr(), self.slot(idx), first);
+        core::ptr::copy_nonoverlapping(src[first..].as_ptr(), self.slot(idx.wrapping_add(first)), src.len() - first);
     }
 
+    /// Copy elements out of the buffer starting at the unbounded index `idx` into `dst`,
+    /// in at most two chunks across the wrap point.
+    ///
+    /// Safety: the caller must have exclusive access to the `dst.len()` initialized slots starting at `idx`
+    unsafe fn copy_out(&self, idx: usize, dst: &mut [T]) where T: Copy {
+        self.cells.read(idx, dst.len());
+        let first = dst.len().min(self.to_wrap(idx));
+        core::ptr::copy_nonoverlapping(self.slot(idx), dst.as_mut_ptr(), first);
+        core::ptr::copy_nonoverlapping(self.slot(idx.wrapping_add(first)), dst[first..].as_mut_ptr(), dst.len() - first);
+    }
+
+    /// Split the buffer into a single producer and a single consumer handle.
+    /// Each handle owns one index and never takes the lock,
+    /// so neither side ever waits on the other.
+    pub fn split(&mut self) -> (Producer<'_, S, L>, Consumer<'_, S, L>) {
+        (Producer::new(self), Consumer::new(self))
+    }
+
+    /// Move the indices of an empty buffer to `idx`,
+    /// so that tests can cross the point where the unbounded indices wrap
+    #[cfg(any(test, loom))]
+    #[doc(hidden)]
+    pub fn start_at(&mut self, idx: usize) {
+        assert!(self.empty());
+        self.read_idx.store(idx, Ordering::Relaxed);
+        self.write_idx.store(idx, Ordering::Relaxed);
+    }
+
+    /// How many elements the buffer can hold
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
 
     /// How much data is available to be read?
     pub fn available(&self) -> usize {
-        let write = self.write_idx.load(Ordering::SeqCst);
-        let read = self.read_idx.load(Ordering::SeqCst);
+        // Retry until `write_idx` is unchanged across the `read_idx` load,
+        // so that both indices describe the same instant.
+        let (write, read) = loop {
+            let write = self.write_idx.load(Ordering::SeqCst);
+            let read = self.read_idx.load(Ordering::SeqCst);
+            if write == self.write_idx.load(Ordering::SeqCst) {
+                break (write, read);
+            }
+        };
         let avail = write.wrapping_sub(read);
-        let read_count = self.read_count.load(Ordering::Relaxed);
-        assert!(avail <= N, "avail: {} write: {} read: {} count: {}", avail, write, read, read_count);
+        assert!(avail <= self.capacity, "avail: {} write: {} read: {}", avail, write, read);
         avail
     }
 
     /// Is the buffer full?
     pub fn full(&self) -> bool {
-        self.available() == N
+        self.available() == self.capacity
     }
 
     /// Is the buffer empty?
@@ -98,70 +297,391 @@ impl<const N: usize> Ringu<N> {
 
     /// At the moment, how much vacant space remains in the buffer?
     pub fn vacant(&self) -> usize {
-        N - self.available()
+        self.capacity - self.available()
     }
 
-    fn lock_if_not_full(&mut self) -> bool {
-        if !self.full() {
+    /// Returns true with the lock held if there is data to read,
+    /// otherwise returns false without holding the lock.
+    fn lock_if_not_empty(&self) -> bool {
+        if !self.empty() {
             self.lock_me();
-            true
+            // another reader may have drained the buffer while we waited
+            if !self.empty() {
+                return true;
+            }
+            self.unlock_me();
         }
-        else {
-            false
+        false
+    }
+
+    /// How many elements have been discarded by the overflow policy?
+    pub fn dropped(&self) -> usize {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Returns true with the lock held if `wanted` elements may now be written,
+    /// after applying the overflow policy.
+    /// Under `OverwriteOldest` this always succeeds, discarding the oldest elements as needed.
+    /// Otherwise it succeeds if there is room for at least one element.
+    fn lock_for_push(&self, wanted: usize) -> bool {
+        if self.overflow != Overflow::OverwriteOldest && self.full() {
+            // no need to wait for the lock just to find out there's no room
+            return false;
         }
+        self.lock_me();
+        if self.make_room(wanted) {
+            return true;
+        }
+        self.unlock_me();
+        false
     }
 
-    fn lock_if_not_empty(&mut self) -> bool {
-        if !self.empty() {
-            self.lock_me();
-            true
+    /// Apply the overflow policy so that `wanted` elements may be written.
+    /// Returns false if there isn't room for even one. Must be called with the lock held.
+    fn make_room(&self, wanted: usize) -> bool {
+        match self.overflow {
+            Overflow::OverwriteOldest => {
+                let excess = (self.available() + wanted).saturating_sub(self.capacity);
+                self.discard_oldest(excess);
+                true
+            }
+            Overflow::Reject | Overflow::DropNewest => {
+                !self.full()
+            }
         }
-        else {
-            false
+    }
+
+    /// Count `count` elements that a push gave up on, as dropped too under `DropNewest`.
+    /// Only the public push methods count: internal retries that will deliver
+    /// the elements later must not.
+    fn reject(&self, count: usize) {
+        if self.overflow == Overflow::DropNewest {
+            self.dropped.fetch_add(count, Ordering::Relaxed);
         }
+        self.stats.rejected(count);
     }
 
-    /// Push one byte into the buffer
-    /// Returns the number of bytes actually pushed (zero or one)
-    pub fn push_one(&mut self, byte: u8) -> usize {
-        if self.lock_if_not_full() {
-            //effectively this reserves space for the write
-            let cur_write_idx = self.write_idx.fetch_add(1, Ordering::SeqCst);
-            self.buf[cur_write_idx & (N - 1)] = byte;
+    /// Drop the `count` oldest elements. Must be called with the lock held.
+    fn discard_oldest(&self, count: usize) {
+        if count == 0 {
+            return;
+        }
+        self.remove_oldest(count);
+        self.dropped.fetch_add(count, Ordering::Relaxed);
+    }
+
+    /// Drop the `count` oldest elements and advance the read index past them.
+    /// Must be called with the lock held, and `count` must not exceed `available()`.
+    fn remove_oldest(&self, count: usize) {
+        let cur_read_idx = self.read_idx.load(Ordering::Relaxed);
+        self.cells.write(cur_read_idx, count);
+        if core::mem::needs_drop::<T>() {
+            for i in 0..count {
+                // Safety: we hold the lock, and these slots are initialized
+                unsafe { self.slot(cur_read_idx.wrapping_add(i)).drop_in_place(); }
+            }
+        }
+        // when overwriting, this moves the read index before the write index,
+        // so `available()` never exceeds the capacity
+        self.read_idx.store(cur_read_idx.wrapping_add(count), Ordering::SeqCst);
+    }
+
+    /// Write one element into a vacant slot and unlock.
+    /// Must be called with the lock held, after making room.
+    fn push_locked(&self, item: T) {
+        let cur_write_idx = self.write_idx.load(Ordering::Relaxed);
+        self.cells.write(cur_write_idx, 1);
+        // Safety: we hold the lock, so nobody else is touching `buf`
+        unsafe { self.slot(cur_write_idx).write(item); }
+        // publish the element only once it is in place
+        self.write_idx.store(cur_write_idx.wrapping_add(1), Ordering::SeqCst);
+        self.stats.wrote(1, self.available());
+        self.unlock_me();
+        self.readable_waker.wake();
+    }
+
+    /// Move the oldest element out and unlock.
+    /// Must be called with the lock held, when the buffer isn't empty.
+    fn pop_locked(&self) -> T {
+        self.stats.read(1);
+        let cur_read_idx = self.read_idx.load(Ordering::Relaxed);
+        self.cells.read(cur_read_idx, 1);
+        // Safety: we hold the lock, and the slot was initialized by a push
+        let item = unsafe { self.slot(cur_read_idx).read() };
+        // release the slot only once the element has been moved out
+        self.read_idx.store(cur_read_idx.wrapping_add(1), Ordering::SeqCst);
+        self.unlock_me();
+        self.writable_waker.wake();
+        item
+    }
+
+    /// Push one element into the buffer
+    /// Returns the element back if the buffer is full and the overflow policy
+    /// doesn't make room for it
+    pub fn try_push(&self, item: T) -> Result<(), Full<T>> {
+        self.push_uncounted(item).inspect_err(|_| self.reject(1))
+    }
+
+    /// [`Ringu::try_push`] without counting a rejection
+    fn push_uncounted(&self, item: T) -> Result<(), Full<T>> {
+        if !self.lock_for_push(1) {
+            return Err(Full(item));
+        }
+        self.push_locked(item);
+        Ok(())
+    }
+
+    /// Remove the oldest element from the buffer, if any
+    pub fn try_pop(&self) -> Option<T> {
+        let item = self.pop_uncounted();
+        if item.is_none() {
+            self.stats.empty_read();
+        }
+        item
+    }
+
+    /// [`Ringu::try_pop`] without counting an empty read
+    fn pop_uncounted(&self) -> Option<T> {
+        if !self.lock_if_not_empty() {
+            return None;
+        }
+        Some(self.pop_locked())
+    }
+
+    /// Like [`Ringu::try_push`], but never waits for the lock.
+    /// Fails with [`Error::WouldBlock`] if the lock is held, for instance by the
+    /// code an interrupt handler preempted, or with [`Error::Full`] if there is no room.
+    /// Either way the element is handed back.
+    /// A `CriticalSectionLock` is never contended, so with one this never fails
+    /// with `WouldBlock`.
+    pub fn push_nowait(&self, item: T) -> Result<(), (Error, T)> {
+        if !self.try_lock_me() {
+            return Err((Error::WouldBlock, item));
+        }
+        if !self.make_room(1) {
+            self.unlock_me();
+            self.reject(1);
+            return Err((Error::Full, item));
+        }
+        self.push_locked(item);
+        Ok(())
+    }
+
+    /// Like [`Ringu::try_pop`], but never waits for the lock.
+    /// Fails with [`Error::WouldBlock`] if the lock is held, or [`Error::Empty`].
+    pub fn pop_nowait(&self) -> Result<T, Error> {
+        if self.empty() {
+            self.stats.empty_read();
+            return Err(Error::Empty);
+        }
+        if !self.try_lock_me() {
+            return Err(Error::WouldBlock);
+        }
+        if self.empty() {
             self.unlock_me();
-            1
+            self.stats.empty_read();
+            return Err(Error::Empty);
+        }
+        Ok(self.pop_locked())
+    }
+
+    /// A snapshot of the usage counters
+    #[cfg(feature = "stats")]
+    pub fn stats(&self) -> Stats {
+        self.stats.snapshot()
+    }
+
+    /// Zero all of the usage counters
+    #[cfg(feature = "stats")]
+    pub fn reset_stats(&self) {
+        self.stats.reset();
+    }
+
+    /// Verify that the indices are consistent, returning [`Error::Corrupted`] if not.
+    /// This can only fail if memory holding the buffer has been clobbered.
+    pub fn check(&self) -> Result<(), Error> {
+        self.lock_me();
+        let write = self.write_idx.load(Ordering::Relaxed);
+        let read = self.read_idx.load(Ordering::Relaxed);
+        self.unlock_me();
+        if write.wrapping_sub(read) > self.capacity {
+            return Err(Error::Corrupted);
+        }
+        Ok(())
+    }
+
+    /// Push as many elements from `src` as currently fit in the buffer
+    /// (or, under `OverwriteOldest`, the last `capacity()` elements of `src`)
+    /// Returns the number of elements actually pushed
+    pub fn push_slice(&self, src: &[T]) -> usize where T: Copy {
+        let count = self.push_slice_uncounted(src);
+        if self.overflow != Overflow::OverwriteOldest {
+            self.reject(src.len() - count);
+        }
+        count
+    }
+
+    /// [`Ringu::push_slice`] without counting the elements that didn't fit
+    fn push_slice_uncounted(&self, src: &[T]) -> usize where T: Copy {
+        if src.is_empty() {
+            return 0;
+        }
+        let mut src = src;
+        if self.overflow == Overflow::OverwriteOldest && src.len() > self.capacity {
+            // the head of `src` would be overwritten by its own tail anyway
+            self.dropped.fetch_add(src.len() - self.capacity, Ordering::Relaxed);
+            src = &src[src.len() - self.capacity..];
+        }
+        if !self.lock_for_push(src.len()) {
+            return 0;
+        }
+        let count = src.len().min(self.vacant());
+        self.push_slice_locked(&src[..count]);
+        count
+    }
+
+    /// Copy `src` in and unlock.
+    /// Must be called with the lock held, with room for all of `src`.
+    fn push_slice_locked(&self, src: &[T]) where T: Copy {
+        let cur_write_idx = self.write_idx.load(Ordering::Relaxed);
+        // Safety: we hold the lock, and `src.len()` slots are vacant
+        unsafe { self.copy_in(cur_write_idx, src); }
+        self.write_idx.store(cur_write_idx.wrapping_add(src.len()), Ordering::SeqCst);
+        self.stats.wrote(src.len(), self.available());
+        self.unlock_me();
+        self.readable_waker.wake();
+    }
+
+    /// Read as many elements as are available, up to the length of `dst`
+    /// Returns the number of elements actually read
+    pub fn read_slice(&self, dst: &mut [T]) -> usize where T: Copy {
+        if dst.is_empty() {
+            return 0;
+        }
+        let count = self.read_slice_uncounted(dst);
+        if count == 0 {
+            self.stats.empty_read();
+        }
+        count
+    }
+
+    /// [`Ringu::read_slice`] without counting an empty read
+    fn read_slice_uncounted(&self, dst: &mut [T]) -> usize where T: Copy {
+        if dst.is_empty() || !self.lock_if_not_empty() {
+            return 0;
+        }
+        let cur_read_idx = self.read_idx.load(Ordering::Relaxed);
+        let count = dst.len().min(self.available());
+        self.stats.read(count);
+        // Safety: we hold the lock, and `count` slots are initialized
+        unsafe { self.copy_out(cur_read_idx, &mut dst[..count]); }
+        self.read_idx.store(cur_read_idx.wrapping_add(count), Ordering::SeqCst);
+        self.unlock_me();
+        self.writable_waker.wake();
+        count
+    }
+
+    /// Copy the oldest element without removing it
+    pub fn peek(&self) -> Option<T> where T: Copy {
+        self.peek_at(0)
+    }
+
+    /// Copy the element `offset` places after the oldest, without removing anything
+    pub fn peek_at(&self, offset: usize) -> Option<T> where T: Copy {
+        self.lock_me();
+        let item = if offset < self.available() {
+            let cur_read_idx = self.read_idx.load(Ordering::Relaxed);
+            self.cells.read(cur_read_idx.wrapping_add(offset), 1);
+            // Safety: we hold the lock, and the slot is initialized
+            Some(unsafe { self.slot(cur_read_idx.wrapping_add(offset)).read() })
         }
         else {
-            0
+            None
+        };
+        self.unlock_me();
+        item
+    }
+
+    /// Copy as many of the oldest elements as fit into `dst`, without removing them
+    /// Returns the number of elements copied
+    pub fn peek_into(&self, dst: &mut [T]) -> usize where T: Copy {
+        if dst.is_empty() || !self.lock_if_not_empty() {
+            return 0;
+        }
+        let cur_read_idx = self.read_idx.load(Ordering::Relaxed);
+        let count = dst.len().min(self.available());
+        // Safety: we hold the lock, and `count` slots are initialized
+        unsafe { self.copy_out(cur_read_idx, &mut dst[..count]); }
+        self.unlock_me();
+        count
+    }
+
+    /// Discard up to `count` of the oldest elements
+    /// Returns the number of elements actually discarded
+    pub fn skip(&self, count: usize) -> usize {
+        if count == 0 || !self.lock_if_not_empty() {
+            return 0;
+        }
+        let count = count.min(self.available());
+        self.stats.read(count);
+        self.remove_oldest(count);
+        self.unlock_me();
+        self.writable_waker.wake();
+        count
+    }
+
+}
+
+impl<S: Storage<Item = u8>, L: RingLock> RingBuf<S, L> {
+    /// Push one byte into the buffer
+    /// Returns the number of bytes actually pushed (zero or one)
+    /// Prefer [`Ringu::try_push`], which can't be mistaken for a count.
+    pub fn push_one(&self, byte: u8) -> usize {
+        match self.try_push(byte) {
+            Ok(()) => 1,
+            Err(_) => 0,
         }
     }
 
     /// Read one byte from the buffer
     /// Returns the number of bytes actually read (zero or one)
     /// and the byte read (if any)
-    pub fn read_one(&mut self) -> (usize, u8) {
-        if self.lock_if_not_empty() {
-            //"reserve" the read
-            self.read_count.fetch_add(1, Ordering::Relaxed);
-            let cur_read_idx = self.read_idx.fetch_add(1, Ordering::SeqCst);
-            let byte = self.buf[cur_read_idx & (N - 1)];
-            self.unlock_me();
-            (1, byte)
-        }
-        else {
-            (0, 0)
+    /// Prefer [`Ringu::try_pop`], which can't confuse "empty" with a zero byte.
+    pub fn read_one(&self) -> (usize, u8) {
+        match self.try_pop() {
+            Some(byte) => (1, byte),
+            None => (0, 0),
         }
     }
+}
 
+impl<T, const N: usize> Default for Ringu<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<S: Storage, L: RingLock> Drop for RingBuf<S, L> {
+    fn drop(&mut self) {
+        if core::mem::needs_drop::<S::Item>() {
+            let write = self.write_idx.load(Ordering::Relaxed);
+            let mut read = self.read_idx.load(Ordering::Relaxed);
+            while read != write {
+                // Safety: slots between the read and write indices are initialized
+                unsafe { self.slot(read).drop_in_place(); }
+                read = read.wrapping_add(1);
+            }
+        }
+    }
 }
 
 
-#[cfg(test)]
+#[cfg(all(test, not(loom)))]
 mod tests {
     use super::*;
     use std::thread;
-    use lazy_static::lazy_static;
-    use core::sync::atomic::{AtomicUsize, AtomicPtr, Ordering::SeqCst};
+    use std::sync::Arc;
+    use core::sync::atomic::{AtomicUsize, Ordering::SeqCst};
 
     // used for testing custom spin func
     fn fake_spin() {
@@ -171,24 +691,17 @@ mod tests {
     /// Test for eventual consistency (number of writes == number reads)
     #[test]
     fn multithread_write_read() {
-        lazy_static!{
-            static ref TOTAL_WRITE_COUNT:AtomicUsize = AtomicUsize::new(0);
-            static ref BLOCKED_WRITE_COUNT:AtomicUsize = AtomicUsize::new(0);
-            static ref BFFL: AtomicPtr<Ringu<256>> = AtomicPtr::default();
-        };
+        static TOTAL_WRITE_COUNT: AtomicUsize = AtomicUsize::new(0);
+        static BLOCKED_WRITE_COUNT: AtomicUsize = AtomicUsize::new(0);
+        static BFFL: Ringu<u8, 256> = Ringu::new_with_spin(fake_spin);
 
         const MAX_WRITE_COUNT: usize = 512;
         const MAX_READ_COUNT: usize = MAX_WRITE_COUNT * 3;
 
-        let mut bffl = Ringu::new_with_spin(fake_spin);
-        BFFL.store(&mut bffl, SeqCst);
-
         let inner_thread = thread::spawn(|| {
             //write more than BUF_LEN size
             for i in 0..MAX_WRITE_COUNT {
-                let n_written = unsafe {
-                    BFFL.load(SeqCst).as_mut().unwrap().push_one((i % 256) as u8 )
-                };
+                let n_written = BFFL.push_one((i % 256) as u8 );
                 TOTAL_WRITE_COUNT.fetch_add(n_written, SeqCst);
                 if 0 == n_written {
                     BLOCKED_WRITE_COUNT.fetch_add(1, SeqCst);
@@ -203,10 +716,7 @@ mod tests {
         let mut outer_read_count = 0;
         let mut prior_read_val: u8 = 255;
         for _ in 0..MAX_READ_COUNT {
-            let (nread, cur_val) =
-                unsafe {
-                    BFFL.load(SeqCst).as_mut().unwrap().read_one()
-                };
+            let (nread, cur_val) = BFFL.read_one();
             read_attempts += 1;
             outer_read_count += nread;
             if nread == 0  {
@@ -228,4 +738,366 @@ mod tests {
         assert_eq!(0, BLOCKED_WRITE_COUNT.load(SeqCst));
     }
 
+    /// Share one buffer between several writers and readers through an `Arc`
+    #[test]
+    fn shared_arc_multi_write_read() {
+        const WRITERS: usize = 4;
+        const PER_WRITER: usize = if cfg!(miri) { 50 } else { 1000 };
+
+        let bffl = Arc::new(Ringu::<u8, 64>::default());
+        let total_read = Arc::new(AtomicUsize::new(0));
+
+        let writers: Vec<_> = (0..WRITERS).map(|_| {
+            let bffl = bffl.clone();
+            thread::spawn(move || {
+                let mut pushed = 0;
+                while pushed < PER_WRITER {
+                    if bffl.push_one(pushed as u8) == 0 {
+                        thread::yield_now();
+                    }
+                    else {
+                        pushed += 1;
+                    }
+                }
+            })
+        }).collect();
+
+        let readers: Vec<_> = (0..2).map(|_| {
+            let bffl = bffl.clone();
+            let total_read = total_read.clone();
+            thread::spawn(move || {
+                while total_read.load(SeqCst) < WRITERS * PER_WRITER {
+                    let (nread, _) = bffl.read_one();
+                    if nread == 0 {
+                        thread::yield_now();
+                    }
+                    total_read.fetch_add(nread, SeqCst);
+                }
+            })
+        }).collect();
+
+        for handle in writers.into_iter().chain(readers) {
+            handle.join().unwrap();
+        }
+        assert_eq!(total_read.load(SeqCst), WRITERS * PER_WRITER);
+        assert!(bffl.empty());
+    }
+
+    /// Heap-owning elements cross threads through a shared buffer,
+    /// and whatever is left over is dropped with it (Miri checks for leaks)
+    #[test]
+    fn shared_arc_boxed_elements() {
+        const PER_WRITER: usize = if cfg!(miri) { 20 } else { 1000 };
+
+        let mut bffl = Arc::new(Ringu::<Box<usize>, 8>::new());
+        let writers: Vec<_> = (0..2).map(|_| {
+            let bffl = bffl.clone();
+            thread::spawn(move || {
+                for i in 0..PER_WRITER {
+                    let mut item = Box::new(i);
+                    while let Err(Full(back)) = bffl.try_push(item) {
+                        item = back;
+                        thread::yield_now();
+                    }
+                }
+            })
+        }).collect();
+
+        // leave some elements behind for the buffer's drop
+        const LEFT: usize = 4;
+        let mut sum = 0;
+        for _ in 0..(2 * PER_WRITER - LEFT) {
+            loop {
+                match bffl.try_pop() {
+                    Some(item) => {
+                        sum += *item;
+                        break;
+                    }
+                    None => thread::yield_now(),
+                }
+            }
+        }
+        for writer in writers {
+            writer.join().unwrap();
+        }
+        assert_eq!(bffl.available(), LEFT);
+        sum += Arc::get_mut(&mut bffl).unwrap().iter().map(|item| **item).sum::<usize>();
+        assert_eq!(sum, PER_WRITER * (PER_WRITER - 1));
+    }
+
+    #[test]
+    fn generic_elements() {
+        #[derive(Debug, PartialEq)]
+        struct Sample {
+            channel: u8,
+            value: i32,
+        }
+
+        let bffl = Ringu::<Sample, 4>::default();
+        for i in 0..4 {
+            assert!(bffl.try_push(Sample { channel: i, value: -(i as i32) }).is_ok());
+        }
+        let rejected = bffl.try_push(Sample { channel: 9, value: 9 });
+        assert_eq!(rejected, Err(Full(Sample { channel: 9, value: 9 })));
+        for i in 0..4 {
+            assert_eq!(bffl.try_pop(), Some(Sample { channel: i, value: -(i as i32) }));
+        }
+        assert_eq!(bffl.try_pop(), None);
+    }
+
+    /// Elements still in the buffer are dropped along with it
+    #[test]
+    fn drop_remaining_elements() {
+        let tracker = Arc::new(());
+        {
+            let bffl = Ringu::<Arc<()>, 8>::default();
+            for _ in 0..6 {
+                bffl.try_push(tracker.clone()).unwrap();
+            }
+            // move the read index so the live region isn't at the array start
+            drop(bffl.try_pop());
+            drop(bffl.try_pop());
+            assert_eq!(Arc::strong_count(&tracker), 5);
+        }
+        assert_eq!(Arc::strong_count(&tracker), 1);
+    }
+
+    #[test]
+    fn slice_push_read_wraps() {
+        let bffl = Ringu::<u8, 8>::default();
+        // offset the indices so that the next bulk copies straddle the wrap point
+        assert_eq!(bffl.push_slice(&[0; 5]), 5);
+        assert_eq!(bffl.read_slice(&mut [0; 5]), 5);
+
+        let src: Vec<u8> = (1..=10).collect();
+        assert_eq!(bffl.push_slice(&src), 8);
+        assert!(bffl.full());
+        assert_eq!(bffl.push_slice(&src), 0);
+
+        let mut dst = [0u8; 3];
+        assert_eq!(bffl.read_slice(&mut dst), 3);
+        assert_eq!(dst, [1, 2, 3]);
+        assert_eq!(bffl.push_slice(&src[8..]), 2);
+
+        let mut dst = [0u8; 16];
+        assert_eq!(bffl.read_slice(&mut dst), 7);
+        assert_eq!(dst[..7], [4, 5, 6, 7, 8, 9, 10]);
+        assert_eq!(bffl.read_slice(&mut dst), 0);
+    }
+
+    #[test]
+    fn overflow_policies() {
+        let reject = Ringu::<u8, 4>::default();
+        assert_eq!(reject.push_slice(&[1, 2, 3, 4, 5, 6]), 4);
+        assert_eq!(reject.push_one(7), 0);
+        assert_eq!(reject.dropped(), 0);
+
+        let drop_newest = Ringu::<u8, 4>::new_with_overflow(Overflow::DropNewest);
+        assert_eq!(drop_newest.push_slice(&[1, 2, 3, 4, 5, 6]), 4);
+        assert_eq!(drop_newest.push_one(7), 0);
+        assert_eq!(drop_newest.dropped(), 3);
+        assert_eq!(drop_newest.read_one(), (1, 1));
+
+        let overwrite = Ringu::<u8, 4>::new_with_overflow(Overflow::OverwriteOldest);
+        assert_eq!(overwrite.push_slice(&[1, 2, 3]), 3);
+        assert_eq!(overwrite.push_one(4), 1);
+        assert_eq!(overwrite.push_one(5), 1);
+        assert_eq!(overwrite.push_slice(&[6, 7]), 2);
+        assert_eq!(overwrite.dropped(), 3);
+        let mut dst = [0u8; 4];
+        assert_eq!(overwrite.read_slice(&mut dst), 4);
+        assert_eq!(dst, [4, 5, 6, 7]);
+
+        assert_eq!(overwrite.push_slice(&[1, 2, 3, 4, 5, 6, 7, 8, 9]), 4);
+        assert_eq!(overwrite.read_slice(&mut dst), 4);
+        assert_eq!(dst, [6, 7, 8, 9]);
+    }
+
+    /// Elements displaced by `OverwriteOldest` are dropped
+    #[test]
+    fn overwrite_drops_displaced() {
+        let tracker = Arc::new(());
+        let bffl = Ringu::<Arc<()>, 2>::new_with_overflow(Overflow::OverwriteOldest);
+        for _ in 0..5 {
+            assert!(bffl.try_push(tracker.clone()).is_ok());
+        }
+        assert_eq!(Arc::strong_count(&tracker), 3);
+        assert_eq!(bffl.dropped(), 3);
+    }
+
+    /// A reader racing an overwriting writer always sees values in order
+    #[test]
+    fn overwrite_with_concurrent_reader() {
+        const COUNT: usize = if cfg!(miri) { 200 } else { 20_000 };
+        let bffl = Ringu::<usize, 16>::new_with_overflow(Overflow::OverwriteOldest);
+
+        thread::scope(|scope| {
+            scope.spawn(|| {
+                for i in 0..COUNT {
+                    assert!(bffl.try_push(i).is_ok());
+                }
+            });
+
+            let mut prior: Option<usize> = None;
+            for _ in 0..COUNT {
+                assert!(bffl.available() <= 16);
+                if let Some(val) = bffl.try_pop() {
+                    // values may be skipped, but never repeated or reordered
+                    assert!(prior.is_none_or(|prior| val > prior));
+                    prior = Some(val);
+                }
+            }
+        });
+    }
+
+    #[test]
+    fn peek_and_skip() {
+        let bffl = Ringu::<u8, 8>::new();
+        assert_eq!(bffl.peek(), None);
+        assert_eq!(bffl.skip(3), 0);
+
+        // a length-prefixed frame, wrapped around the end of the array
+        bffl.push_slice(&[0; 6]);
+        bffl.skip(6);
+        bffl.push_slice(&[3, b'a', b'b']);
+        assert_eq!(bffl.peek(), Some(3));
+        assert_eq!(bffl.peek_at(2), Some(b'b'));
+        assert_eq!(bffl.peek_at(3), None);
+
+        let mut header = [0u8; 2];
+        assert_eq!(bffl.peek_into(&mut header), 2);
+        assert_eq!(header, [3, b'a']);
+        assert_eq!(bffl.available(), 3);
+
+        // the frame is incomplete, so wait; then it arrives and is consumed
+        bffl.push_one(b'c');
+        assert_eq!(bffl.skip(1), 1);
+        let mut body = [0u8; 3];
+        assert_eq!(bffl.read_slice(&mut body), 3);
+        assert_eq!(&body, b"abc");
+        assert_eq!(bffl.skip(10), 0);
+    }
+
+    #[test]
+    fn skip_drops_elements() {
+        let tracker = Arc::new(());
+        let bffl = Ringu::<Arc<()>, 4>::new();
+        for _ in 0..3 {
+            bffl.try_push(tracker.clone()).unwrap();
+        }
+        assert_eq!(bffl.skip(2), 2);
+        assert_eq!(Arc::strong_count(&tracker), 2);
+        assert_eq!(bffl.dropped(), 0);
+    }
+
+    #[test]
+    fn result_types() {
+        let bffl = Ringu::<u8, 2>::new();
+        assert_eq!(bffl.try_pop(), None);
+        assert_eq!(bffl.pop_nowait(), Err(Error::Empty));
+        assert_eq!(bffl.try_push(1), Ok(()));
+        assert_eq!(bffl.push_nowait(2), Ok(()));
+        assert_eq!(bffl.try_push(3), Err(Full(3)));
+        assert_eq!(bffl.push_nowait(3), Err((Error::Full, 3)));
+        let err: Error = bffl.try_push(3).unwrap_err().into();
+        assert_eq!(err, Error::Full);
+
+        // pretend another context is holding the lock
+        bffl.lock_me();
+        assert_eq!(bffl.pop_nowait(), Err(Error::WouldBlock));
+        assert_eq!(bffl.push_nowait(3), Err((Error::WouldBlock, 3)));
+        bffl.unlock_me();
+
+        assert_eq!(bffl.pop_nowait(), Ok(1));
+        assert_eq!(bffl.try_pop(), Some(2));
+        assert_eq!(bffl.check(), Ok(()));
+
+        bffl.write_idx.store(100, SeqCst);
+        assert_eq!(bffl.check(), Err(Error::Corrupted));
+    }
+
+    /// Interrupt handlers run with the critical section already held;
+    /// the buffer's own critical section nests inside it.
+    #[cfg(feature = "critical-section")]
+    #[test]
+    fn inside_outer_critical_section() {
+        static BFFL: Ringu<u8, 4, CriticalSectionLock> =
+            Ringu::new_with_lock(CriticalSectionLock::INIT, Overflow::Reject);
+        critical_section::with(|_| {
+            assert_eq!(BFFL.push_one(1), 1);
+            assert_eq!(BFFL.push_slice(&[2, 3]), 2);
+        });
+        assert_eq!(BFFL.read_one(), (1, 1));
+        critical_section::with(|_| {
+            assert_eq!(BFFL.try_pop(), Some(2));
+            assert_eq!(BFFL.pop_nowait(), Ok(3));
+        });
+        assert!(BFFL.empty());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn mutex_lock_multi_write_read() {
+        const COUNT: usize = if cfg!(miri) { 50 } else { 1000 };
+        let bffl = Arc::new(Ringu::<usize, 8, MutexLock>::new_with_lock(MutexLock::INIT, Overflow::Reject));
+        let writers: Vec<_> = (0..2).map(|_| {
+            let bffl = bffl.clone();
+            thread::spawn(move || {
+                for i in 0..COUNT {
+                    while bffl.try_push(i).is_err() {
+                        thread::yield_now();
+                    }
+                }
+            })
+        }).collect();
+        let mut sum = 0;
+        let mut total = 0;
+        while total < 2 * COUNT {
+            match bffl.try_pop() {
+                Some(i) => {
+                    sum += i;
+                    total += 1;
+                }
+                None => thread::yield_now(),
+            }
+        }
+        for writer in writers {
+            writer.join().unwrap();
+        }
+        assert_eq!(sum, COUNT * (COUNT - 1));
+    }
+
+    #[test]
+    fn null_lock_single_thread() {
+        let mut bffl: Ringu<u8, 4, NullLock> = [1, 2, 3].into_iter().collect();
+        assert_eq!(bffl.push_slice(&[4, 5]), 1);
+        assert_eq!(bffl.pop_nowait(), Ok(1));
+        assert!(bffl.iter().copied().eq([2, 3, 4]));
+        let (mut producer, mut consumer) = bffl.split();
+        assert_eq!(producer.try_push(6), Ok(()));
+        assert_eq!(consumer.try_pop(), Some(2));
+    }
+
+    #[test]
+    fn indices_wrap_around_usize() {
+        let mut bffl = Ringu::<u8, 4>::new();
+        bffl.start_at(usize::MAX - 2);
+        assert_eq!(bffl.push_slice(&[1, 2, 3, 4, 5]), 4);
+        assert!(bffl.full());
+        assert_eq!(bffl.check(), Ok(()));
+        assert!(bffl.iter().copied().eq([1, 2, 3, 4]));
+        assert_eq!(bffl.peek_at(3), Some(4));
+        let mut dst = [0; 3];
+        assert_eq!(bffl.read_slice(&mut dst), 3);
+        assert_eq!(dst, [1, 2, 3]);
+        assert_eq!(bffl.push_slice(&[6, 7, 8]), 3);
+        assert_eq!(bffl.available(), 4);
+
+        let (mut producer, mut consumer) = bffl.split();
+        assert_eq!(producer.try_push(9), Err(Full(9)));
+        assert_eq!(consumer.read_slice(&mut [0; 4]), 4);
+        assert_eq!(producer.push_slice(&[10, 11]), 2);
+        assert_eq!(consumer.try_pop(), Some(10));
+    }
+
 }
@@ -0,0 +1,311 @@
+/*
+Copyright (c) 2022 Todd Stellanova
+LICENSE: BSD3 (see LICENSE file)
+*/
+
+//! Lock strategies for [`Ringu`](crate::Ringu).
+//!
+//! Every index and storage update on a shared `Ringu` happens while holding its lock.
+//! The lock type is a generic parameter, so each deployment can pick the primitive
+//! that suits it: the default [`SpinLock`], a [`MutexLock`] that parks blocked threads,
+//! a [`CriticalSectionLock`] that masks interrupts, a [`NullLock`] for single-threaded use,
+//! or its own implementation wrapping e.g. an RTOS semaphore.
+
+use core::cell::Cell;
+use core::marker::PhantomData;
+use crate::sync::{const_fn, lock_init, spin_loop, AtomicBool, Ordering};
+
+/// A function called on each turn of a busy-wait loop
+pub type SpinFunc = fn();
+
+/// A raw lock guarding a [`Ringu`](crate::Ringu).
+///
+/// # Safety
+///
+/// If the implementing type is `Sync`, then between a `lock` (or a successful `try_lock`)
+/// and the matching `unlock`, no other context may acquire the same lock.
+/// A lock that is not `Sync` keeps its buffer on a single thread,
+/// so it may grant every request.
+pub unsafe trait RingLock {
+    /// Acquire the lock, waiting as long as necessary.
+    /// Returns how many turns it spent waiting (spins, or sleeps for a blocking lock),
+    /// which feeds the buffer's `lock_spins` counter.
+    fn lock(&self) -> usize;
+
+    /// Acquire the lock only if that doesn't require waiting
+    fn try_lock(&self) -> bool;
+
+    /// Release the lock.
+    ///
+    /// # Safety
+    ///
+    /// Must only be called by the context currently holding the lock.
+    unsafe fn unlock(&self);
+
+    /// Called on each turn while busy-waiting for a full or empty buffer to change
+    fn relax(&self) {
+        spin_loop();
+    }
+}
+
+/// A [`RingLock`] that needs no runtime handle or configuration,
+/// so the constructors that don't take a lock can build one themselves.
+/// A lock wrapping, say, an RTOS semaphore handle implements only [`RingLock`]
+/// and is passed to a `_with_lock` constructor instead.
+pub trait LockInit: RingLock {
+    /// An unlocked lock, so buffers can be built in a `const` context
+    #[cfg(not(loom))]
+    const INIT: Self;
+
+    /// An unlocked lock (loom atomics can't be built in a `const` context)
+    #[cfg(loom)]
+    fn init() -> Self;
+}
+
+/// An unlocked `L`, however this build constructs one
+#[cfg(not(loom))]
+pub(crate) fn unlocked<L: LockInit>() -> L {
+    L::INIT
+}
+
+#[cfg(loom)]
+pub(crate) fn unlocked<L: LockInit>() -> L {
+    L::init()
+}
+
+/// The default lock: spins on an atomic flag, calling a [`SpinFunc`] while it waits
+pub struct SpinLock {
+    locked: AtomicBool,
+    spin: SpinFunc,
+}
+
+impl SpinLock {
+    const_fn! {
+        /// A spin lock that calls `spin` on each turn while it waits
+        pub fn new_with_spin(spin: SpinFunc) -> Self {
+            Self {
+                locked: AtomicBool::new(false),
+                spin,
+            }
+        }
+    }
+}
+
+impl LockInit for SpinLock {
+    lock_init!(Self::new_with_spin(spin_loop));
+}
+
+unsafe impl RingLock for SpinLock {
+    fn lock(&self) -> usize {
+        let mut spins = 0;
+        while self.locked
+            .compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_err() {
+            while self.locked.load(Ordering::Relaxed) {
+                spins += 1;
+                (self.spin)();
+            }
+        }
+        spins
+    }
+
+    fn try_lock(&self) -> bool {
+        self.locked
+            .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_ok()
+    }
+
+    unsafe fn unlock(&self) {
+        self.locked.store(false, Ordering::Release);
+    }
+
+    fn relax(&self) {
+        (self.spin)();
+    }
+}
+
+/// No locking at all, for a buffer that never leaves one thread.
+/// This type is not `Sync`, so neither is a `Ringu` using it.
+pub struct NullLock(PhantomData<Cell<()>>);
+
+impl LockInit for NullLock {
+    lock_init!(Self(PhantomData));
+}
+
+unsafe impl RingLock for NullLock {
+    fn lock(&self) -> usize {
+        0
+    }
+
+    fn try_lock(&self) -> bool {
+        true
+    }
+
+    unsafe fn unlock(&self) {}
+}
+
+/// A lock that puts waiting threads to sleep, built on [`std::sync::Mutex`]
+#[cfg(feature = "std")]
+pub struct MutexLock {
+    locked: std::sync::Mutex<bool>,
+    unlocked: std::sync::Condvar,
+}
+
+#[cfg(feature = "std")]
+impl MutexLock {
+    /// The flag only changes under the mutex, so a panic elsewhere can't leave it inconsistent
+    fn flag(&self) -> std::sync::MutexGuard<'_, bool> {
+        self.locked.lock().unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+}
+
+#[cfg(feature = "std")]
+impl LockInit for MutexLock {
+    lock_init!(Self {
+        locked: std::sync::Mutex::new(false),
+        unlocked: std::sync::Condvar::new(),
+    });
+}
+
+#[cfg(feature = "std")]
+unsafe impl RingLock for MutexLock {
+    fn lock(&self) -> usize {
+        let mut locked = self.flag();
+        let mut sleeps = 0;
+        while *locked {
+            sleeps += 1;
+            locked = self.unlocked.wait(locked).unwrap_or_else(std::sync::PoisonError::into_inner);
+        }
+        *locked = true;
+        sleeps
+    }
+
+    fn try_lock(&self) -> bool {
+        let mut locked = self.flag();
+        !core::mem::replace(&mut *locked, true)
+    }
+
+    unsafe fn unlock(&self) {
+        *self.flag() = false;
+        self.unlocked.notify_one();
+    }
+
+    fn relax(&self) {
+        std::thread::yield_now();
+    }
+}
+
+/// A lock that enters a [`critical_section`] for each update,
+/// so thread mode and interrupt handlers can share a buffer on single-core MCUs
+/// without deadlock. Entering a critical section never has to wait.
+#[cfg(feature = "critical-section")]
+pub struct CriticalSectionLock {
+    /// How to restore interrupts (or whatever the critical section masked) on unlock.
+    /// Only accessed from inside the critical section.
+    restore: core::cell::UnsafeCell<critical_section::RestoreState>,
+}
+
+// Safety: `restore` is only accessed from inside the critical section
+#[cfg(feature = "critical-section")]
+unsafe impl Sync for CriticalSectionLock {}
+
+#[cfg(feature = "critical-section")]
+impl LockInit for CriticalSectionLock {
+    lock_init!(Self {
+        restore: core::cell::UnsafeCell::new(critical_section::RestoreState::invalid()),
+    });
+}
+
+#[cfg(feature = "critical-section")]
+unsafe impl RingLock for CriticalSectionLock {
+    fn lock(&self) -> usize {
+        // Safety: every `lock` is paired with an `unlock`, in nesting order
+        let restore = unsafe { critical_section::acquire() };
+        // Safety: we're inside the critical section
+        unsafe { *self.restore.get() = restore; }
+        0
+    }
+
+    fn try_lock(&self) -> bool {
+        self.lock();
+        true
+    }
+
+    unsafe fn unlock(&self) {
+        // Safety: we're still inside the critical section entered by `lock`
+        critical_section::release(*self.restore.get());
+    }
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spin_lock_excludes() {
+        let lock = SpinLock::INIT;
+        assert!(lock.try_lock());
+        assert!(!lock.try_lock());
+        unsafe { lock.unlock() };
+        assert_eq!(lock.lock(), 0);
+        assert!(!lock.try_lock());
+        unsafe { lock.unlock() };
+    }
+
+    #[test]
+    fn spin_lock_counts_spins() {
+        let lock = SpinLock::new_with_spin(std::thread::yield_now);
+        lock.lock();
+        let spins = std::thread::scope(|scope| {
+            let waiter = scope.spawn(|| {
+                let spins = lock.lock();
+                unsafe { lock.unlock() };
+                spins
+            });
+            std::thread::sleep(std::time::Duration::from_millis(10));
+            unsafe { lock.unlock() };
+            waiter.join().unwrap()
+        });
+        assert!(spins > 0);
+    }
+
+    /// A lock that only exists once it is handed a runtime resource
+    struct SharedLock<'a>(&'a SpinLock);
+
+    unsafe impl RingLock for SharedLock<'_> {
+        fn lock(&self) -> usize {
+            self.0.lock()
+        }
+
+        fn try_lock(&self) -> bool {
+            self.0.try_lock()
+        }
+
+        unsafe fn unlock(&self) {
+            self.0.unlock()
+        }
+    }
+
+    #[test]
+    fn lock_built_at_runtime() {
+        let handle = SpinLock::INIT;
+        let bffl = crate::Ringu::<u8, 4, _>::new_with_lock(SharedLock(&handle), crate::Overflow::Reject);
+        assert_eq!(bffl.push_slice(&[1, 2]), 2);
+        assert!(handle.try_lock());
+        assert!(!bffl.lock.try_lock());
+        unsafe { handle.unlock() };
+        assert_eq!(bffl.read_one(), (1, 1));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn mutex_lock_excludes() {
+        let lock = MutexLock::INIT;
+        assert!(lock.try_lock());
+        assert!(!lock.try_lock());
+        unsafe { lock.unlock() };
+        assert_eq!(lock.lock(), 0);
+        assert!(!lock.try_lock());
+        unsafe { lock.unlock() };
+    }
+}
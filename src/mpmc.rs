@@ -0,0 +1,294 @@
+/*
+Copyright (c) 2022 Todd Stellanova
+LICENSE: BSD3 (see LICENSE file)
+*/
+
+//! Lock-free multi-producer, multi-consumer ring buffer.
+//!
+//! Each slot carries a sequence number that tells producers and consumers
+//! whose turn it is to touch that slot, so they only ever contend on a
+//! compare-and-swap of `write_idx` or `read_idx`, never on a global lock.
+
+use crate::sync::{const_fn, AtomicUsize, Ordering, UnsafeCell};
+
+struct Slot {
+    /// Equal to the unbounded write index that may fill this slot next,
+    /// or to that index plus one once the slot holds a byte ready to read.
+    seq: AtomicUsize,
+    byte: UnsafeCell<u8>,
+}
+
+/// A ring buffer that many threads may push to and read from without taking a lock.
+///
+/// `N` must be a non-zero power of two; anything else fails to compile:
+///
+/// ```compile_fail
+/// let bffl = ringu::MpmcRingu::<0>::new();
+/// ```
+pub struct MpmcRingu<const N: usize> {
+    slots: [Slot; N],
+
+    /// The index at which the next byte should be read from the buffer
+    /// This grows unbounded until it wraps, and is only masked into
+    /// the inner buffer range when we access the array.
+    read_idx: AtomicUsize,
+
+    /// The index at which the next byte should be written to the buffer
+    /// This grows unbounded until it wraps, and is only masked into
+    /// the inner buffer range when we access the array.
+    write_idx: AtomicUsize,
+}
+
+// Safety: a slot's byte is only accessed by the single thread that won the
+// index CAS for it, and the slot's sequence number hands it over with acquire/release.
+unsafe impl<const N: usize> Sync for MpmcRingu<N> {}
+
+impl<const N: usize> MpmcRingu<N> {
+    /// Indices are masked into the array with `N - 1`,
+    /// which only works for a non-zero power of two.
+    const CAPACITY_OK: () = assert!(N.is_power_of_two(), "MpmcRingu capacity N must be a non-zero power of two");
+
+    const_fn! {
+        /// Create an empty buffer.
+        /// This is a `const fn`, so an `MpmcRingu` can be placed in a plain `static`.
+        pub fn new() -> Self {
+            // reject a bad capacity at compile time, when the constructor is instantiated
+            let () = Self::CAPACITY_OK;
+            #[cfg(not(loom))]
+            let slots = {
+                let mut slots = [const { Slot { seq: AtomicUsize::new(0), byte: UnsafeCell::new(0) } }; N];
+                let mut i = 0;
+                while i < N {
+                    slots[i].seq = AtomicUsize::new(i);
+                    i += 1;
+                }
+                slots
+            };
+            #[cfg(loom)]
+            let slots = core::array::from_fn(|i| Slot { seq: AtomicUsize::new(i), byte: UnsafeCell::new(0) });
+            Self {
+                slots,
+                read_idx: AtomicUsize::new(0),
+                write_idx: AtomicUsize::new(0),
+            }
+        }
+    }
+
+    /// Move the indices of an empty buffer to `idx`,
+    /// so that tests can cross the point where the unbounded indices wrap
+    #[cfg(any(test, loom))]
+    #[doc(hidden)]
+    pub fn start_at(&mut self, idx: usize) {
+        assert!(self.empty());
+        for i in 0..N {
+            let pos = idx.wrapping_add(i);
+            self.slot(pos).seq.store(pos, Ordering::Relaxed);
+        }
+        self.read_idx.store(idx, Ordering::Relaxed);
+        self.write_idx.store(idx, Ordering::Relaxed);
+    }
+
+    fn slot(&self, idx: usize) -> &Slot {
+        &self.slots[idx & (N - 1)]
+    }
+
+    /// How much data is available to be read?
+    /// This counts slots that writers have claimed but may not have finished filling.
+    pub fn available(&self) -> usize {
+        let (write, read) = loop {
+            let write = self.write_idx.load(Ordering::SeqCst);
+            let read = self.read_idx.load(Ordering::SeqCst);
+            if write == self.write_idx.load(Ordering::SeqCst) {
+                break (write, read);
+            }
+        };
+        write.wrapping_sub(read)
+    }
+
+    /// Is the buffer full?
+    pub fn full(&self) -> bool {
+        self.available() == N
+    }
+
+    /// Is the buffer empty?
+    pub fn empty(&self) -> bool {
+        self.available() == 0
+    }
+
+    /// At the moment, how much vacant space remains in the buffer?
+    pub fn vacant(&self) -> usize {
+        N - self.available()
+    }
+
+    /// Push one byte into the buffer
+    /// Returns the number of bytes actually pushed (zero or one)
+    pub fn push_one(&self, byte: u8) -> usize {
+        let mut pos = self.write_idx.load(Ordering::Relaxed);
+        loop {
+            let slot = self.slot(pos);
+            let seq = slot.seq.load(Ordering::Acquire);
+            let diff = seq.wrapping_sub(pos) as isize;
+            if diff == 0 {
+                // the slot is vacant for this lap: try to claim it
+                match self.write_idx.compare_exchange_weak(
+                    pos, pos.wrapping_add(1), Ordering::Relaxed, Ordering::Relaxed) {
+                    Ok(_) => {
+                        // Safety: winning the CAS gives us sole access to this slot
+                        slot.byte.with_mut(|slot_byte| unsafe { *slot_byte = byte });
+                        slot.seq.store(pos.wrapping_add(1), Ordering::Release);
+                        return 1;
+                    }
+                    Err(actual) => pos = actual,
+                }
+            }
+            else if diff < 0 {
+                // the slot still holds a byte from the previous lap: we're full
+                return 0;
+            }
+            else {
+                // another writer claimed this slot first
+                pos = self.write_idx.load(Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Read one byte from the buffer
+    /// Returns the number of bytes actually read (zero or one)
+    /// and the byte read (if any)
+    pub fn read_one(&self) -> (usize, u8) {
+        let mut pos = self.read_idx.load(Ordering::Relaxed);
+        loop {
+            let slot = self.slot(pos);
+            let seq = slot.seq.load(Ordering::Acquire);
+            let diff = seq.wrapping_sub(pos.wrapping_add(1)) as isize;
+            if diff == 0 {
+                // the slot has been filled for this lap: try to claim it
+                match self.read_idx.compare_exchange_weak(
+                    pos, pos.wrapping_add(1), Ordering::Relaxed, Ordering::Relaxed) {
+                    Ok(_) => {
+                        // Safety: winning the CAS gives us sole access to this slot
+                        let byte = slot.byte.with(|slot_byte| unsafe { *slot_byte });
+                        // hand the slot to the writer one lap ahead
+                        slot.seq.store(pos.wrapping_add(N), Ordering::Release);
+                        return (1, byte);
+                    }
+                    Err(actual) => pos = actual,
+                }
+            }
+            else if diff < 0 {
+                // nothing written to this slot yet: we're empty
+                return (0, 0);
+            }
+            else {
+                // another reader claimed this slot first
+                pos = self.read_idx.load(Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+impl<const N: usize> Default for MpmcRingu<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+    use std::thread;
+
+    #[test]
+    fn single_thread_wraparound() {
+        let bffl = MpmcRingu::<8>::new();
+        for lap in 0..10u8 {
+            for i in 0..8 {
+                assert_eq!(bffl.push_one(lap.wrapping_add(i)), 1);
+            }
+            assert!(bffl.full());
+            assert_eq!(bffl.push_one(0), 0);
+            for i in 0..8 {
+                assert_eq!(bffl.read_one(), (1, lap.wrapping_add(i)));
+            }
+            assert_eq!(bffl.read_one(), (0, 0));
+        }
+    }
+
+    #[test]
+    fn indices_wrap_around_usize() {
+        let mut bffl = MpmcRingu::<4>::new();
+        bffl.start_at(usize::MAX - 1);
+        for lap in 0..3u8 {
+            for i in 0..4 {
+                assert_eq!(bffl.push_one(lap * 4 + i), 1);
+            }
+            assert!(bffl.full());
+            assert_eq!(bffl.push_one(0), 0);
+            for i in 0..4 {
+                assert_eq!(bffl.read_one(), (1, lap * 4 + i));
+            }
+            assert_eq!(bffl.read_one(), (0, 0));
+        }
+    }
+
+    #[test]
+    fn const_static() {
+        static BFFL: MpmcRingu<4> = MpmcRingu::new();
+        for i in 0..4 {
+            assert_eq!(BFFL.push_one(i), 1);
+        }
+        assert_eq!(BFFL.push_one(4), 0);
+        assert_eq!(BFFL.read_one(), (1, 0));
+    }
+
+    /// Several writers and several readers hammer the same buffer;
+    /// every byte written must be read exactly once.
+    #[test]
+    fn multithread_multi_write_read() {
+        const WRITERS: usize = 4;
+        const READERS: usize = 4;
+        // a multiple of 256, so every byte value is written equally often
+        const PER_WRITER: usize = if cfg!(miri) { 256 } else { 4096 };
+        const TOTAL: usize = WRITERS * PER_WRITER;
+
+        let bffl = MpmcRingu::<64>::new();
+        let total_read = AtomicUsize::new(0);
+        let histogram: Vec<AtomicUsize> = (0..256).map(|_| AtomicUsize::new(0)).collect();
+
+        thread::scope(|scope| {
+            for _ in 0..WRITERS {
+                scope.spawn(|| {
+                    let mut i = 0;
+                    while i < PER_WRITER {
+                        if bffl.push_one((i % 256) as u8) == 1 {
+                            i += 1;
+                        }
+                        else {
+                            thread::yield_now();
+                        }
+                    }
+                });
+            }
+            for _ in 0..READERS {
+                scope.spawn(|| {
+                    while total_read.load(Ordering::SeqCst) < TOTAL {
+                        let (nread, val) = bffl.read_one();
+                        if nread == 0 {
+                            thread::yield_now();
+                            continue;
+                        }
+                        histogram[val as usize].fetch_add(1, Ordering::SeqCst);
+                        total_read.fetch_add(1, Ordering::SeqCst);
+                    }
+                });
+            }
+        });
+
+        assert_eq!(total_read.load(Ordering::SeqCst), TOTAL);
+        for count in histogram.iter() {
+            assert_eq!(count.load(Ordering::SeqCst), TOTAL / 256);
+        }
+        assert!(bffl.empty());
+    }
+}
@@ -0,0 +1,254 @@
+/*
+Copyright (c) 2022 Todd Stellanova
+LICENSE: BSD3 (see LICENSE file)
+*/
+
+//! Single-producer, single-consumer handles for a [`Ringu`].
+//!
+//! The producer is the only writer of `write_idx` and the consumer is the only
+//! writer of `read_idx`, so acquire/release ordering on the two indices
+//! is enough to hand bytes across without the spin lock.
+
+use crate::sync::Ordering;
+
+use crate::{Full, RingBuf, RingLock, SpinLock, Storage};
+#[cfg(feature = "stats")]
+use crate::Stats;
+
+/// The writing half of a split [`RingBuf`]
+pub struct Producer<'a, S: Storage, L: RingLock = SpinLock> {
+    ring: &'a RingBuf<S, L>,
+}
+
+/// The reading half of a split [`RingBuf`]
+pub struct Consumer<'a, S: Storage, L: RingLock = SpinLock> {
+    ring: &'a RingBuf<S, L>,
+}
+
+impl<'a, T, S: Storage<Item = T>, L: RingLock> Producer<'a, S, L> {
+    pub(crate) fn new(ring: &'a RingBuf<S, L>) -> Self {
+        Self { ring }
+    }
+
+    pub(crate) fn ring(&self) -> &'a RingBuf<S, L> {
+        self.ring
+    }
+
+    /// Push one element into the buffer
+    /// Returns the element back if the buffer is full
+    pub fn try_push(&mut self, item: T) -> Result<(), Full<T>> {
+        let write = self.ring.write_idx.load(Ordering::Relaxed);
+        let read = self.ring.read_idx.load(Ordering::Acquire);
+        let occupied = write.wrapping_sub(read);
+        if occupied == self.ring.capacity() {
+            self.ring.stats.rejected(1);
+            return Err(Full(item));
+        }
+        self.ring.cells.write(write, 1);
+        // Safety: the slot is vacant and only this producer writes vacant slots
+        unsafe { self.ring.slot(write).write(item); }
+        self.ring.write_idx.store(write.wrapping_add(1), Ordering::Release);
+        self.ring.stats.wrote(1, occupied + 1);
+        Ok(())
+    }
+
+    /// Push as many elements from `src` as currently fit in the buffer
+    /// Returns the number of elements actually pushed
+    pub fn push_slice(&mut self, src: &[T]) -> usize where T: Copy {
+        let write = self.ring.write_idx.load(Ordering::Relaxed);
+        let vacant = self.vacant();
+        let count = src.len().min(vacant);
+        // Safety: these slots are vacant and only this producer writes vacant slots
+        unsafe { self.ring.copy_in(write, &src[..count]); }
+        self.ring.write_idx.store(write.wrapping_add(count), Ordering::Release);
+        self.ring.stats.rejected(src.len() - count);
+        self.ring.stats.wrote(count, self.ring.capacity() - vacant + count);
+        count
+    }
+
+    /// At the moment, how much vacant space remains in the buffer?
+    pub fn vacant(&self) -> usize {
+        let write = self.ring.write_idx.load(Ordering::Relaxed);
+        let read = self.ring.read_idx.load(Ordering::Acquire);
+        self.ring.capacity() - write.wrapping_sub(read)
+    }
+
+    /// Is the buffer full?
+    pub fn full(&self) -> bool {
+        self.vacant() == 0
+    }
+
+    /// A snapshot of the buffer's usage counters
+    #[cfg(feature = "stats")]
+    pub fn stats(&self) -> Stats {
+        self.ring.stats()
+    }
+}
+
+impl<'a, T, S: Storage<Item = T>, L: RingLock> Consumer<'a, S, L> {
+    pub(crate) fn new(ring: &'a RingBuf<S, L>) -> Self {
+        Self { ring }
+    }
+
+    pub(crate) fn ring(&self) -> &'a RingBuf<S, L> {
+        self.ring
+    }
+
+    /// Remove the oldest element from the buffer, if any
+    pub fn try_pop(&mut self) -> Option<T> {
+        let read = self.ring.read_idx.load(Ordering::Relaxed);
+        let write = self.ring.write_idx.load(Ordering::Acquire);
+        if read == write {
+            self.ring.stats.empty_read();
+            return None;
+        }
+        self.ring.cells.read(read, 1);
+        // Safety: the slot was initialized and published by the producer's release store
+        let item = unsafe { self.ring.slot(read).read() };
+        self.ring.read_idx.store(read.wrapping_add(1), Ordering::Release);
+        self.ring.stats.read(1);
+        Some(item)
+    }
+
+    /// Read as many elements as are available, up to the length of `dst`
+    /// Returns the number of elements actually read
+    pub fn read_slice(&mut self, dst: &mut [T]) -> usize where T: Copy {
+        let read = self.ring.read_idx.load(Ordering::Relaxed);
+        let count = dst.len().min(self.available());
+        if count == 0 && !dst.is_empty() {
+            self.ring.stats.empty_read();
+        }
+        // Safety: these slots were initialized and published by the producer
+        unsafe { self.ring.copy_out(read, &mut dst[..count]); }
+        self.ring.read_idx.store(read.wrapping_add(count), Ordering::Release);
+        self.ring.stats.read(count);
+        count
+    }
+
+    /// How much data is available to be read?
+    pub fn available(&self) -> usize {
+        let read = self.ring.read_idx.load(Ordering::Relaxed);
+        let write = self.ring.write_idx.load(Ordering::Acquire);
+        write.wrapping_sub(read)
+    }
+
+    /// Is the buffer empty?
+    pub fn empty(&self) -> bool {
+        self.available() == 0
+    }
+
+    /// A snapshot of the buffer's usage counters
+    #[cfg(feature = "stats")]
+    pub fn stats(&self) -> Stats {
+        self.ring.stats()
+    }
+}
+
+impl<S: Storage<Item = u8>, L: RingLock> Producer<'_, S, L> {
+    /// Push one byte into the buffer
+    /// Returns the number of bytes actually pushed (zero or one)
+    pub fn push_one(&mut self, byte: u8) -> usize {
+        match self.try_push(byte) {
+            Ok(()) => 1,
+            Err(_) => 0,
+        }
+    }
+}
+
+impl<S: Storage<Item = u8>, L: RingLock> Consumer<'_, S, L> {
+    /// Read one byte from the buffer
+    /// Returns the number of bytes actually read (zero or one)
+    /// and the byte read (if any)
+    pub fn read_one(&mut self) -> (usize, u8) {
+        match self.try_pop() {
+            Some(byte) => (1, byte),
+            None => (0, 0),
+        }
+    }
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use crate::Ringu;
+    use std::thread;
+
+    #[test]
+    fn spsc_threads() {
+        const COUNT: usize = if cfg!(miri) { 200 } else { 10_000 };
+        let mut bffl = Ringu::<u8, 32>::default();
+        let (mut producer, mut consumer) = bffl.split();
+
+        thread::scope(|scope| {
+            scope.spawn(move || {
+                let mut i = 0;
+                while i < COUNT {
+                    if producer.push_one(i as u8) == 1 {
+                        i += 1;
+                    }
+                    else {
+                        thread::yield_now();
+                    }
+                }
+            });
+
+            let mut expected = 0;
+            while expected < COUNT {
+                let (nread, val) = consumer.read_one();
+                if nread == 0 {
+                    thread::yield_now();
+                    continue;
+                }
+                assert_eq!(val, expected as u8);
+                expected += 1;
+            }
+            assert!(consumer.empty());
+        });
+    }
+
+    #[test]
+    fn full_and_empty() {
+        let mut bffl = Ringu::<u8, 4>::default();
+        let (mut producer, mut consumer) = bffl.split();
+        for i in 0..4 {
+            assert_eq!(producer.push_one(i), 1);
+        }
+        assert!(producer.full());
+        assert_eq!(producer.push_one(4), 0);
+        assert_eq!(consumer.available(), 4);
+        for i in 0..4 {
+            assert_eq!(consumer.read_one(), (1, i));
+        }
+        assert_eq!(consumer.read_one(), (0, 0));
+        assert_eq!(producer.vacant(), 4);
+    }
+
+    #[test]
+    fn slices_across_threads() {
+        const COUNT: usize = if cfg!(miri) { 200 } else { 10_000 };
+        let mut bffl = Ringu::<u8, 64>::default();
+        let (mut producer, mut consumer) = bffl.split();
+
+        thread::scope(|scope| {
+            scope.spawn(move || {
+                let src: Vec<u8> = (0..COUNT).map(|i| i as u8).collect();
+                let mut sent = 0;
+                while sent < COUNT {
+                    let end = (sent + 50).min(COUNT);
+                    sent += producer.push_slice(&src[sent..end]);
+                    thread::yield_now();
+                }
+            });
+
+            let mut received = 0;
+            let mut dst = [0u8; 37];
+            while received < COUNT {
+                let nread = consumer.read_slice(&mut dst);
+                for (i, val) in dst[..nread].iter().enumerate() {
+                    assert_eq!(*val, (received + i) as u8);
+                }
+                received += nread;
+                thread::yield_now();
+            }
+        });
+    }
+}
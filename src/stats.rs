@@ -0,0 +1,184 @@
+/*
+Copyright (c) 2022 Todd Stellanova
+LICENSE: BSD3 (see LICENSE file)
+*/
+
+//! Usage counters, for sizing buffers on real hardware.
+//!
+//! The counters are relaxed atomics maintained alongside every push and read.
+//! Disabling the default `stats` feature compiles them out entirely.
+
+#[cfg(feature = "stats")]
+use crate::sync::{const_fn, AtomicUsize, Ordering};
+
+/// A snapshot of a buffer's counters, from [`Ringu::stats`](crate::Ringu::stats)
+#[cfg(feature = "stats")]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Stats {
+    /// Total elements pushed
+    pub written: usize,
+    /// Total elements read (or skipped)
+    pub read: usize,
+    /// Elements refused because the buffer was full
+    pub rejected: usize,
+    /// Reads attempted while the buffer was empty
+    pub empty_reads: usize,
+    /// The most elements the buffer has held at once
+    pub high_water: usize,
+    /// Spins spent waiting for the lock
+    pub lock_spins: usize,
+}
+
+#[cfg(feature = "stats")]
+pub(crate) struct Counters {
+    written: AtomicUsize,
+    read: AtomicUsize,
+    rejected: AtomicUsize,
+    empty_reads: AtomicUsize,
+    high_water: AtomicUsize,
+    lock_spins: AtomicUsize,
+}
+
+#[cfg(feature = "stats")]
+impl Counters {
+    const_fn! {
+        pub(crate) fn new() -> Self {
+            Self {
+                written: AtomicUsize::new(0),
+                read: AtomicUsize::new(0),
+                rejected: AtomicUsize::new(0),
+                empty_reads: AtomicUsize::new(0),
+                high_water: AtomicUsize::new(0),
+                lock_spins: AtomicUsize::new(0),
+            }
+        }
+    }
+
+    /// `count` elements were pushed, leaving `occupied` in the buffer
+    pub(crate) fn wrote(&self, count: usize, occupied: usize) {
+        self.written.fetch_add(count, Ordering::Relaxed);
+        self.high_water.fetch_max(occupied, Ordering::Relaxed);
+    }
+
+    pub(crate) fn read(&self, count: usize) {
+        self.read.fetch_add(count, Ordering::Relaxed);
+    }
+
+    pub(crate) fn rejected(&self, count: usize) {
+        self.rejected.fetch_add(count, Ordering::Relaxed);
+    }
+
+    pub(crate) fn empty_read(&self) {
+        self.empty_reads.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn spun(&self, spins: usize) {
+        // most locks are uncontended, so don't pay for an atomic add of zero
+        if spins != 0 {
+            self.lock_spins.fetch_add(spins, Ordering::Relaxed);
+        }
+    }
+
+    pub(crate) fn snapshot(&self) -> Stats {
+        Stats {
+            written: self.written.load(Ordering::Relaxed),
+            read: self.read.load(Ordering::Relaxed),
+            rejected: self.rejected.load(Ordering::Relaxed),
+            empty_reads: self.empty_reads.load(Ordering::Relaxed),
+            high_water: self.high_water.load(Ordering::Relaxed),
+            lock_spins: self.lock_spins.load(Ordering::Relaxed),
+        }
+    }
+
+    pub(crate) fn reset(&self) {
+        self.written.store(0, Ordering::Relaxed);
+        self.read.store(0, Ordering::Relaxed);
+        self.rejected.store(0, Ordering::Relaxed);
+        self.empty_reads.store(0, Ordering::Relaxed);
+        self.high_water.store(0, Ordering::Relaxed);
+        self.lock_spins.store(0, Ordering::Relaxed);
+    }
+}
+
+/// With the `stats` feature disabled, every counter update is a no-op
+#[cfg(not(feature = "stats"))]
+pub(crate) struct Counters;
+
+#[cfg(not(feature = "stats"))]
+impl Counters {
+    pub(crate) const fn new() -> Self {
+        Self
+    }
+
+    pub(crate) fn wrote(&self, _count: usize, _occupied: usize) {}
+
+    pub(crate) fn read(&self, _count: usize) {}
+
+    pub(crate) fn rejected(&self, _count: usize) {}
+
+    pub(crate) fn empty_read(&self) {}
+
+    pub(crate) fn spun(&self, _spins: usize) {}
+}
+
+#[cfg(all(test, feature = "stats", not(loom)))]
+mod tests {
+    use crate::{Overflow, Ringu};
+
+    #[test]
+    fn counts_traffic() {
+        let bffl = Ringu::<u8, 4>::new();
+        assert_eq!(bffl.try_pop(), None);
+        assert_eq!(bffl.push_slice(&[1, 2, 3]), 3);
+        assert_eq!(bffl.read_slice(&mut [0; 2]), 2);
+        assert_eq!(bffl.push_slice(&[4, 5, 6, 7]), 3);
+        assert_eq!(bffl.push_one(8), 0);
+        assert_eq!(bffl.skip(1), 1);
+
+        let stats = bffl.stats();
+        assert_eq!(stats.written, 6);
+        assert_eq!(stats.read, 3);
+        assert_eq!(stats.rejected, 2);
+        assert_eq!(stats.empty_reads, 1);
+        assert_eq!(stats.high_water, 4);
+
+        bffl.reset_stats();
+        assert_eq!(bffl.stats(), Default::default());
+    }
+
+    #[test]
+    fn counts_lock_spins() {
+        use crate::{RingLock, SpinLock};
+        use std::thread;
+
+        let bffl: Ringu<u8, 4> = Ringu::new_with_lock(SpinLock::new_with_spin(thread::yield_now), Overflow::Reject);
+        assert_eq!(bffl.push_one(1), 1);
+        assert_eq!(bffl.stats().lock_spins, 0);
+
+        bffl.lock.lock();
+        thread::scope(|scope| {
+            scope.spawn(|| bffl.push_one(2));
+            thread::sleep(std::time::Duration::from_millis(10));
+            unsafe { bffl.lock.unlock() };
+        });
+        assert!(bffl.stats().lock_spins > 0);
+    }
+
+    #[test]
+    fn split_halves_share_counters() {
+        let mut bffl = Ringu::<u8, 4>::new_with_overflow(Overflow::DropNewest);
+        let (mut producer, mut consumer) = bffl.split();
+        assert_eq!(producer.push_slice(&[1, 2, 3, 4, 5]), 4);
+        assert_eq!(consumer.read_one(), (1, 1));
+        assert_eq!(consumer.read_slice(&mut [0; 8]), 3);
+        assert_eq!(consumer.read_one(), (0, 0));
+
+        let stats = consumer.stats();
+        assert_eq!(stats, producer.stats());
+        assert_eq!(stats.written, 4);
+        assert_eq!(stats.read, 4);
+        assert_eq!(stats.rejected, 1);
+        assert_eq!(stats.empty_reads, 1);
+        assert_eq!(stats.high_water, 4);
+    }
+}
@@ -0,0 +1,165 @@
+/*
+Copyright (c) 2022 Todd Stellanova
+LICENSE: BSD3 (see LICENSE file)
+*/
+
+//! [`std::io`] traits for byte buffers, for host-side tools and simulators.
+//!
+//! Like the standard library's own in-memory readers and writers, these never
+//! block: a read from an empty buffer returns `Ok(0)`, and a write into a full
+//! buffer returns `Ok(0)` (which `write_all` reports as `WriteZero`).
+//!
+//! `BufRead` lends out part of the buffer, so it is implemented on the split
+//! [`Consumer`], the only reader that can guarantee the region won't change
+//! underneath the borrower. (On `Ringu` itself it would also shadow
+//! [`Ringu::split`] with `BufRead::split`.)
+
+use std::io::{self, BufRead, Read, Write};
+
+use crate::sync::Ordering;
+use crate::{Consumer, Producer, RingBuf, RingLock, Storage};
+
+impl<S: Storage<Item = u8>, L: RingLock> Read for RingBuf<S, L> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        Ok(self.read_slice(buf))
+    }
+}
+
+impl<S: Storage<Item = u8>, L: RingLock> Read for &RingBuf<S, L> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        Ok(self.read_slice(buf))
+    }
+}
+
+impl<S: Storage<Item = u8>, L: RingLock> RingBuf<S, L> {
+    /// Push a prefix of `buf`, without counting the rest as rejected or dropped:
+    /// a short write only asks the caller to send the remainder later.
+    fn write_prefix(&self, buf: &[u8]) -> usize {
+        // a longer write would keep only its tail under `OverwriteOldest`,
+        // but the count we return has to describe a prefix of `buf`
+        self.push_slice_uncounted(&buf[..buf.len().min(self.capacity)])
+    }
+}
+
+impl<S: Storage<Item = u8>, L: RingLock> Write for RingBuf<S, L> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        Ok(self.write_prefix(buf))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<S: Storage<Item = u8>, L: RingLock> Write for &RingBuf<S, L> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        Ok(self.write_prefix(buf))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<S: Storage<Item = u8>, L: RingLock> Read for Consumer<'_, S, L> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        Ok(self.read_slice(buf))
+    }
+}
+
+impl<S: Storage<Item = u8>, L: RingLock> BufRead for Consumer<'_, S, L> {
+    /// The readable bytes up to the wrap point
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        let ring = self.ring();
+        let read = ring.read_idx.load(Ordering::Relaxed);
+        let len = self.available().min(ring.to_wrap(read));
+        ring.cells.read(read, len);
+        // Safety: the producer won't touch these published slots until we consume them
+        Ok(unsafe { std::slice::from_raw_parts(ring.slot(read), len) })
+    }
+
+    fn consume(&mut self, amt: usize) {
+        let amt = amt.min(self.available());
+        let ring = self.ring();
+        let read = ring.read_idx.load(Ordering::Relaxed);
+        ring.read_idx.store(read.wrapping_add(amt), Ordering::Release);
+        ring.stats.read(amt);
+    }
+}
+
+impl<S: Storage<Item = u8>, L: RingLock> Write for Producer<'_, S, L> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        Ok(self.push_slice(buf))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use super::*;
+    use crate::{Overflow, Ringu};
+
+    #[test]
+    fn write_and_copy() {
+        let mut bffl = Ringu::<u8, 32>::new();
+        write!(bffl, "first\nsecond\n").unwrap();
+        assert_eq!(bffl.write(&[b'x'; 64]).unwrap(), 19);
+        assert_eq!(bffl.write(b"more").unwrap(), 0);
+
+        let mut out = Vec::new();
+        io::copy(&mut &bffl, &mut out).unwrap();
+        assert_eq!(out.len(), 32);
+        assert!(out.starts_with(b"first\nsecond\nxxx"));
+        assert!(bffl.empty());
+    }
+
+    #[test]
+    fn write_all_keeps_stream_order_when_overwriting() {
+        let mut bffl = Ringu::<u8, 4>::new_with_overflow(Overflow::OverwriteOldest);
+        bffl.write_all(&[1, 2, 3, 4, 5, 6, 7, 8, 9]).unwrap();
+        let mut dst = [0; 4];
+        assert_eq!(bffl.read_slice(&mut dst), 4);
+        assert_eq!(dst, [6, 7, 8, 9]);
+    }
+
+    #[test]
+    fn short_write_is_not_dropped() {
+        let mut bffl = Ringu::<u8, 4>::new_with_overflow(Overflow::DropNewest);
+        assert_eq!(bffl.write(&[1, 2, 3, 4, 5, 6]).unwrap(), 4);
+        assert_eq!(bffl.write(&[5, 6]).unwrap(), 0);
+        assert_eq!(bffl.dropped(), 0);
+    }
+
+    #[test]
+    fn consumer_lines() {
+        let mut bffl = Ringu::<u8, 32>::new();
+        let (mut producer, consumer) = bffl.split();
+        write!(producer, "one\ntwo\nthree").unwrap();
+        let lines: Vec<String> = consumer.lines().map(Result::unwrap).collect();
+        assert_eq!(lines, ["one", "two", "three"]);
+    }
+
+    #[test]
+    fn consumer_fill_buf_wraps() {
+        let mut bffl = Ringu::<u8, 8>::new();
+        let (mut producer, mut consumer) = bffl.split();
+        producer.write_all(&[0; 6]).unwrap();
+        consumer.consume(6);
+        producer.write_all(b"abcde").unwrap();
+
+        // the first segment stops at the wrap point
+        assert_eq!(consumer.fill_buf().unwrap(), b"ab");
+        consumer.consume(1);
+        assert_eq!(consumer.fill_buf().unwrap(), b"b");
+        consumer.consume(1);
+        assert_eq!(consumer.fill_buf().unwrap(), b"cde");
+
+        let mut dst = String::new();
+        consumer.read_to_string(&mut dst).unwrap();
+        assert_eq!(dst, "cde");
+        assert_eq!(consumer.fill_buf().unwrap(), b"");
+    }
+}
@@ -0,0 +1,274 @@
+/*
+Copyright (c) 2022 Todd Stellanova
+LICENSE: BSD3 (see LICENSE file)
+*/
+
+//! The memory a [`RingBuf`](crate::RingBuf) keeps its elements in.
+//!
+//! Inline slots give the const-constructible [`Ringu`](crate::Ringu);
+//! borrowed byte slices give [`SliceRingu`](crate::SliceRingu), for memory placed elsewhere;
+//! with the `alloc` feature, heap slots give the runtime-sized
+//! `HeapRingu`. Either way the buffer logic is the same.
+//! Any other [`Storage`] can be wrapped with [`RingBuf::from_storage`].
+
+#[cfg(feature = "alloc")]
+use core::mem::ManuallyDrop;
+use core::mem::MaybeUninit;
+
+#[cfg(feature = "alloc")]
+use alloc::{boxed::Box, vec::Vec};
+
+use crate::lock::unlocked;
+use crate::{Overflow, RingBuf, RingLock};
+
+/// Backing memory for a ring buffer: a run of element slots, found through a base pointer.
+///
+/// Implemented for `[u8; N]`, `&mut [u8]`, the slots inside [`Ringu`](crate::Ringu)
+/// and, with the `alloc` feature, `Box<[u8]>`, `Vec<u8>` and the slots inside `HeapRingu`.
+/// Other memory can be used by implementing it, upholding the contract below.
+///
+/// # Safety
+///
+/// [`Storage::slots`] must return a pointer valid for reads and writes of
+/// [`Storage::capacity`] elements for as long as the storage is neither moved nor dropped.
+/// Byte storage must hold initialized bytes, since write grants expose it as `&mut [u8]`.
+///
+/// A shared buffer calls `slots` from whichever thread is using it, possibly from two
+/// threads at once (the split halves), so `slots` must not write to the storage itself.
+/// The buffer is only `Sync` if the storage is `Send`.
+pub unsafe trait Storage {
+    /// The element type
+    type Item;
+
+    /// How many elements fit.
+    /// A buffer uses the largest power of two that is no greater.
+    fn capacity(&self) -> usize;
+
+    /// Pointer to the first slot.
+    /// Takes a raw pointer so that no reference ever covers slots another thread may be writing.
+    ///
+    /// # Safety
+    ///
+    /// `this` must point to a live `Self`.
+    unsafe fn slots(this: *mut Self) -> *mut Self::Item;
+}
+
+/// The slots inside a [`Ringu`](crate::Ringu), stored inline.
+/// Elements are only initialized between the read and write indices,
+/// so these can't be built outside this crate: `Ringu`'s constructors zero them,
+/// which keeps every byte slot initialized.
+#[repr(transparent)]
+pub struct InlineSlots<T, const N: usize>([MaybeUninit<T>; N]);
+
+impl<T, const N: usize> InlineSlots<T, N> {
+    /// Zeroed slots: valid bytes, for byte buffers
+    pub(crate) const fn zeroed() -> Self {
+        // Safety: an array of `MaybeUninit` is valid for any bit pattern
+        Self(unsafe { MaybeUninit::zeroed().assume_init() })
+    }
+}
+
+unsafe impl<T, const N: usize> Storage for InlineSlots<T, N> {
+    type Item = T;
+
+    fn capacity(&self) -> usize {
+        N
+    }
+
+    unsafe fn slots(this: *mut Self) -> *mut T {
+        this.cast()
+    }
+}
+
+unsafe impl<const N: usize> Storage for [u8; N] {
+    type Item = u8;
+
+    fn capacity(&self) -> usize {
+        N
+    }
+
+    unsafe fn slots(this: *mut Self) -> *mut u8 {
+        this.cast()
+    }
+}
+
+unsafe impl Storage for &mut [u8] {
+    type Item = u8;
+
+    fn capacity(&self) -> usize {
+        self.len()
+    }
+
+    unsafe fn slots(this: *mut Self) -> *mut u8 {
+        // a place expression through the reference, so no new reference to the slots is created
+        core::ptr::addr_of_mut!(**this).cast()
+    }
+}
+
+/// The slots inside a `HeapRingu`, on the heap.
+/// Like [`InlineSlots`], only `HeapRingu`'s constructors build these, zeroed.
+#[cfg(feature = "alloc")]
+pub struct HeapSlots<T>(Box<[MaybeUninit<T>]>);
+
+#[cfg(feature = "alloc")]
+impl<T> HeapSlots<T> {
+    /// `capacity` zeroed slots
+    pub(crate) fn zeroed(capacity: usize) -> Self {
+        Self((0..capacity).map(|_| MaybeUninit::zeroed()).collect())
+    }
+}
+
+#[cfg(feature = "alloc")]
+unsafe impl<T> Storage for HeapSlots<T> {
+    type Item = T;
+
+    fn capacity(&self) -> usize {
+        self.0.len()
+    }
+
+    unsafe fn slots(this: *mut Self) -> *mut T {
+        // a place expression through the box, so no reference to the slots is created
+        core::ptr::addr_of_mut!(*(*this).0).cast()
+    }
+}
+
+#[cfg(feature = "alloc")]
+unsafe impl Storage for Box<[u8]> {
+    type Item = u8;
+
+    fn capacity(&self) -> usize {
+        self.len()
+    }
+
+    unsafe fn slots(this: *mut Self) -> *mut u8 {
+        core::ptr::addr_of_mut!(**this).cast()
+    }
+}
+
+#[cfg(feature = "alloc")]
+unsafe impl Storage for Vec<u8> {
+    type Item = u8;
+
+    /// The length, not the allocated capacity: only the first `len()` bytes are initialized
+    fn capacity(&self) -> usize {
+        self.len()
+    }
+
+    unsafe fn slots(this: *mut Self) -> *mut u8 {
+        // Borrowing the vector mutably would race with the other half of a split buffer,
+        // so read a copy of its header instead; the buffer never resizes the vector,
+        // so the bytes never move. `as_mut_ptr` creates no reference to the bytes.
+        ManuallyDrop::new(core::ptr::read(this)).as_mut_ptr()
+    }
+}
+
+impl<S: Storage> RingBuf<S> {
+    /// Wrap `buf`, using the largest power-of-two number of its slots,
+    /// like [`SliceRingu::from_slice`](crate::SliceRingu::from_slice).
+    ///
+    /// ```
+    /// let bffl = ringu::RingBuf::from_storage([0u8; 100]);
+    /// assert_eq!(bffl.capacity(), 64);
+    /// assert_eq!(bffl.push_slice(b"hello"), 5);
+    /// ```
+    ///
+    /// Storage must be initialized, so uninitialized slots are refused:
+    ///
+    /// ```compile_fail
+    /// use core::mem::MaybeUninit;
+    /// let bffl = ringu::RingBuf::from_storage([MaybeUninit::<u8>::uninit(); 8]);
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// If `buf` has no slots.
+    pub fn from_storage(buf: S) -> Self {
+        Self::from_storage_with_lock(buf, unlocked(), Overflow::Reject)
+    }
+}
+
+impl<S: Storage, L: RingLock> RingBuf<S, L> {
+    /// Wrap `buf`, using the largest power-of-two number of its slots,
+    /// guarded by `lock` and with the given overflow policy.
+    ///
+    /// # Panics
+    ///
+    /// If `buf` has no slots.
+    pub fn from_storage_with_lock(buf: S, lock: L, overflow: Overflow) -> Self {
+        let slots = buf.capacity();
+        assert!(slots != 0, "storage needs at least one slot");
+        Self::with_storage(buf, 1 << slots.ilog2(), lock, overflow)
+    }
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn byte_array_storage() {
+        let mut bffl = RingBuf::from_storage([0u8; 4]);
+        assert_eq!(bffl.push_slice(&[1, 2, 3, 4, 5]), 4);
+        assert_eq!(bffl.iter().copied().collect::<Vec<_>>(), [1, 2, 3, 4]);
+        let mut dest = [0; 4];
+        assert_eq!(bffl.read_slice(&mut dest), 4);
+        assert_eq!(dest, [1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn uses_power_of_two_prefix() {
+        let bffl = RingBuf::from_storage([0u8; 6]);
+        assert_eq!(bffl.capacity(), 4);
+        assert_eq!(bffl.push_slice(&[1; 6]), 4);
+    }
+
+    #[test]
+    #[should_panic(expected = "at least one slot")]
+    fn rejects_empty_storage() {
+        let _ = RingBuf::from_storage([0u8; 0]);
+    }
+
+    #[test]
+    fn chosen_lock_and_overflow() {
+        let bffl: RingBuf<[u8; 2], crate::NullLock> =
+            RingBuf::from_storage_with_lock([0; 2], unlocked(), Overflow::OverwriteOldest);
+        assert_eq!(bffl.push_slice(&[1, 2, 3]), 2);
+        assert_eq!(bffl.dropped(), 1);
+        assert_eq!(bffl.read_one(), (1, 2));
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn boxed_byte_storage() {
+        let mut bffl = RingBuf::from_storage(Box::<[u8]>::from([0; 4]));
+        assert_eq!(bffl.capacity(), 4);
+        assert_eq!(bffl.push_slice(&[1, 2, 3]), 3);
+        assert_eq!(bffl.read_slice(&mut [0; 2]), 2);
+        let (mut producer, mut consumer) = bffl.split();
+        // the write grant stops at the end of the box
+        let mut grant = producer.grant_write(4);
+        assert_eq!(grant.buf().len(), 1);
+        grant.buf()[0] = 4;
+        grant.commit(1);
+        assert_eq!(producer.push_slice(&[5, 6]), 2);
+        let grant = consumer.grant_read();
+        assert_eq!(grant.bufs(), (&[3, 4][..], &[5, 6][..]));
+        grant.release(4);
+        assert!(consumer.empty());
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn vec_byte_storage() {
+        let mut bytes = Vec::with_capacity(100);
+        bytes.resize(12, 0xaa);
+        let mut bffl = RingBuf::from_storage(bytes);
+        // the length sets the capacity, not the allocation
+        assert_eq!(bffl.capacity(), 8);
+        assert!(bffl.empty());
+        assert_eq!(bffl.push_slice(&[1; 10]), 8);
+        assert_eq!(bffl.peek_at(7), Some(1));
+        bffl.extend(b"more");
+        assert_eq!(bffl.available(), 8);
+    }
+}
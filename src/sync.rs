@@ -0,0 +1,122 @@
+/*
+Copyright (c) 2022 Todd Stellanova
+LICENSE: BSD3 (see LICENSE file)
+*/
+
+//! The atomics and cells used throughout the crate.
+//!
+//! Built with `RUSTFLAGS="--cfg loom"`, these come from [loom](https://docs.rs/loom),
+//! which model-checks the concurrency tests in `tests/loom.rs` over every
+//! interleaving rather than whichever ones the scheduler happens to produce,
+//! and checks that every access to a cell is ordered against the others.
+//! Loom atomics can't be built in a `const` context, so under loom the
+//! constructors declared with [`const_fn!`] lose their `const`.
+//! They also only work inside a loom model, so the unit tests are left out of loom builds.
+
+#[cfg(not(loom))]
+pub(crate) use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+#[cfg(loom)]
+pub(crate) use loom::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+#[cfg(loom)]
+pub(crate) use loom::cell::UnsafeCell;
+
+/// [`core::cell::UnsafeCell`] behind loom's closure-based API
+#[cfg(not(loom))]
+pub(crate) struct UnsafeCell<T>(core::cell::UnsafeCell<T>);
+
+#[cfg(not(loom))]
+impl<T> UnsafeCell<T> {
+    pub(crate) const fn new(data: T) -> Self {
+        Self(core::cell::UnsafeCell::new(data))
+    }
+
+    /// Read through the cell's pointer
+    pub(crate) fn with<R>(&self, f: impl FnOnce(*const T) -> R) -> R {
+        f(self.0.get())
+    }
+
+    /// Write through the cell's pointer
+    pub(crate) fn with_mut<R>(&self, f: impl FnOnce(*mut T) -> R) -> R {
+        f(self.0.get())
+    }
+}
+
+/// Loom's view of a ring buffer's slots.
+///
+/// The slots live in `Storage` memory that loom can't see, and the split halves
+/// touch different slots at once, so one cell around the storage won't do.
+/// Instead each slot gets a tracked cell of its own, touched alongside every
+/// access to the slot, so that loom checks each access is ordered against
+/// the previous ones. Without loom this is empty and every method is a no-op.
+pub(crate) struct SlotCells {
+    #[cfg(loom)]
+    cells: std::vec::Vec<UnsafeCell<()>>,
+}
+
+impl SlotCells {
+    #[cfg(not(loom))]
+    pub(crate) const fn new(_capacity: usize) -> Self {
+        Self {}
+    }
+
+    #[cfg(loom)]
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self { cells: (0..capacity).map(|_| UnsafeCell::new(())).collect() }
+    }
+
+    /// `count` slots from the unbounded index `idx` are being read
+    #[cfg_attr(not(loom), allow(unused_variables))]
+    pub(crate) fn read(&self, idx: usize, count: usize) {
+        #[cfg(loom)]
+        for i in 0..count {
+            self.cells[idx.wrapping_add(i) & (self.cells.len() - 1)].with(|_| ());
+        }
+    }
+
+    /// `count` slots from the unbounded index `idx` are being written
+    #[cfg_attr(not(loom), allow(unused_variables))]
+    pub(crate) fn write(&self, idx: usize, count: usize) {
+        #[cfg(loom)]
+        for i in 0..count {
+            self.cells[idx.wrapping_add(i) & (self.cells.len() - 1)].with_mut(|_| ());
+        }
+    }
+}
+
+/// The default busy-wait step; under loom it also lets the other threads run
+pub(crate) fn spin_loop() {
+    #[cfg(not(loom))]
+    core::hint::spin_loop();
+    #[cfg(loom)]
+    loom::hint::spin_loop();
+}
+
+/// Declare a function that is `const` except under loom
+macro_rules! const_fn {
+    ($(#[$attr:meta])* $vis:vis fn $($rest:tt)*) => {
+        $(#[$attr])*
+        #[cfg(not(loom))]
+        $vis const fn $($rest)*
+
+        $(#[$attr])*
+        #[cfg(loom)]
+        $vis fn $($rest)*
+    };
+}
+
+/// Define [`LockInit::INIT`](crate::LockInit::INIT), which loom builds at runtime instead
+macro_rules! lock_init {
+    ($init:expr) => {
+        #[cfg(not(loom))]
+        const INIT: Self = $init;
+
+        #[cfg(loom)]
+        fn init() -> Self {
+            $init
+        }
+    };
+}
+
+pub(crate) use const_fn;
+pub(crate) use lock_init;
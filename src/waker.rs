@@ -0,0 +1,273 @@
+/*
+Copyright (c) 2022 Todd Stellanova
+LICENSE: BSD3 (see LICENSE file)
+*/
+
+//! Async push and pop for [`Ringu`].
+//!
+//! Each `Ringu` holds one waker slot per direction: a task waiting for data,
+//! and a task waiting for room. A successful push wakes the reader and a
+//! successful read wakes the writer. Registering a new waker replaces the old
+//! one, so only one task per direction should be awaiting at a time.
+
+use core::future::poll_fn;
+use core::task::{Context, Poll, Waker};
+
+use crate::sync::{const_fn, AtomicUsize, Ordering, UnsafeCell};
+use crate::{Full, RingBuf, RingLock, Storage};
+
+/// Nobody is touching the waker
+const WAITING: usize = 0;
+/// `register` is replacing the waker
+const REGISTERING: usize = 0b01;
+/// `wake` is taking the waker
+const WAKING: usize = 0b10;
+
+/// A slot holding at most one [`Waker`], which may be registered and woken
+/// concurrently from different threads (or an interrupt handler) without a lock.
+pub(crate) struct AtomicWaker {
+    state: AtomicUsize,
+    waker: UnsafeCell<Option<Waker>>,
+}
+
+// Safety: `waker` is only accessed by whoever moved `state` out of WAITING
+unsafe impl Send for AtomicWaker {}
+unsafe impl Sync for AtomicWaker {}
+
+impl AtomicWaker {
+    const_fn! {
+        pub(crate) fn new() -> Self {
+            Self {
+                state: AtomicUsize::new(WAITING),
+                waker: UnsafeCell::new(None),
+            }
+        }
+    }
+
+    /// Store `waker` to be woken by the next call to `wake`
+    pub(crate) fn register(&self, waker: &Waker) {
+        match self.state
+            .compare_exchange(WAITING, REGISTERING, Ordering::Acquire, Ordering::Acquire)
+            .unwrap_or_else(|state| state) {
+            WAITING => {
+                // Safety: we hold the REGISTERING bit, so nobody else touches `waker`
+                self.waker.with_mut(|slot| unsafe {
+                    if !(*slot).as_ref().is_some_and(|old| old.will_wake(waker)) {
+                        *slot = Some(waker.clone());
+                    }
+                });
+                if self.state
+                    .compare_exchange(REGISTERING, WAITING, Ordering::AcqRel, Ordering::Acquire)
+                    .is_err() {
+                    // a wake arrived while we were registering: deliver it ourselves
+                    // Safety: WAKING leaves `waker` to us while REGISTERING is still set
+                    let waker = self.waker.with_mut(|slot| unsafe { (*slot).take() });
+                    self.state.swap(WAITING, Ordering::AcqRel);
+                    if let Some(waker) = waker {
+                        waker.wake();
+                    }
+                }
+            }
+            WAKING => {
+                // a wake is in progress and may miss the new waker: wake right away
+                waker.wake_by_ref();
+            }
+            _ => {
+                // a concurrent register is in progress; that one wins
+            }
+        }
+    }
+
+    /// Wake the registered task, if any
+    pub(crate) fn wake(&self) {
+        if self.state.fetch_or(WAKING, Ordering::AcqRel) == WAITING {
+            // Safety: we moved the state out of WAITING, so nobody else touches `waker`
+            let waker = self.waker.with_mut(|slot| unsafe { (*slot).take() });
+            self.state.fetch_and(!WAKING, Ordering::Release);
+            if let Some(waker) = waker {
+                waker.wake();
+            }
+        }
+    }
+}
+
+impl<T, S: Storage<Item = T>, L: RingLock> RingBuf<S, L> {
+    /// Run `attempt`, and if it isn't ready register for a wake from `waker`
+    /// and run it once more, so that a wake arriving in between isn't lost.
+    /// Attempts use the uncounted paths: an element that has to wait isn't rejected or dropped.
+    fn poll_with<R>(cx: &mut Context<'_>, waker: &AtomicWaker, mut attempt: impl FnMut() -> Option<R>) -> Poll<R> {
+        if let Some(done) = attempt() {
+            return Poll::Ready(done);
+        }
+        waker.register(cx.waker());
+        match attempt() {
+            Some(done) => Poll::Ready(done),
+            None => Poll::Pending,
+        }
+    }
+
+    /// Push one element, waiting for room if the buffer is full
+    pub async fn push_async(&self, item: T) {
+        let mut item = Some(item);
+        poll_fn(|cx| Self::poll_with(cx, &self.writable_waker, || {
+            match self.push_uncounted(item.take()?) {
+                Ok(()) => Some(()),
+                Err(Full(back)) => {
+                    item = Some(back);
+                    None
+                }
+            }
+        })).await
+    }
+
+    /// Remove the oldest element, waiting for one to arrive if the buffer is empty
+    pub async fn pop_async(&self) -> T {
+        poll_fn(|cx| Self::poll_with(cx, &self.readable_waker, || self.pop_uncounted())).await
+    }
+
+    /// Push as many elements from `src` as fit, waiting until at least one does.
+    /// Returns the number of elements actually pushed, which is zero only if `src` is empty.
+    pub async fn push_slice_async(&self, src: &[T]) -> usize where T: Copy {
+        if src.is_empty() {
+            return 0;
+        }
+        poll_fn(|cx| Self::poll_with(cx, &self.writable_waker, || {
+            match self.push_slice_uncounted(src) {
+                0 => None,
+                count => Some(count),
+            }
+        })).await
+    }
+
+    /// Read as many elements as are available into `dst`, waiting until at least one is.
+    /// Returns the number of elements actually read, which is zero only if `dst` is empty.
+    pub async fn read_slice_async(&self, dst: &mut [T]) -> usize where T: Copy {
+        if dst.is_empty() {
+            return 0;
+        }
+        poll_fn(|cx| Self::poll_with(cx, &self.readable_waker, || {
+            match self.read_slice_uncounted(dst) {
+                0 => None,
+                count => Some(count),
+            }
+        })).await
+    }
+}
+
+#[cfg(all(test, not(loom)))]
+pub(crate) mod tests {
+    use super::*;
+    use crate::Ringu;
+    use core::future::Future;
+    use core::pin::pin;
+    use std::sync::Arc;
+    use std::task::Wake;
+    use std::thread::{self, Thread};
+
+    struct ThreadWaker(Thread);
+
+    impl Wake for ThreadWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    /// Minimal executor: poll `fut` on this thread, parking between wakes
+    pub(crate) fn block_on<F: Future>(fut: F) -> F::Output {
+        let mut fut = pin!(fut);
+        let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+        let mut cx = Context::from_waker(&waker);
+        loop {
+            if let Poll::Ready(out) = fut.as_mut().poll(&mut cx) {
+                return out;
+            }
+            thread::park();
+        }
+    }
+
+    #[derive(Default)]
+    struct CountingWaker(AtomicUsize);
+
+    impl Wake for CountingWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn pop_waits_for_push() {
+        let bffl = Ringu::<u8, 4>::new();
+        let counter = Arc::new(CountingWaker::default());
+        let waker = Waker::from(counter.clone());
+        let mut cx = Context::from_waker(&waker);
+
+        let mut pop = pin!(bffl.pop_async());
+        assert_eq!(pop.as_mut().poll(&mut cx), Poll::Pending);
+        assert_eq!(counter.0.load(Ordering::SeqCst), 0);
+
+        bffl.push_one(42);
+        assert_eq!(counter.0.load(Ordering::SeqCst), 1);
+        assert_eq!(pop.as_mut().poll(&mut cx), Poll::Ready(42));
+    }
+
+    #[test]
+    fn waiting_push_is_not_dropped() {
+        let bffl = Ringu::<u8, 2>::new_with_overflow(crate::Overflow::DropNewest);
+        bffl.push_slice(&[1, 2]);
+        let waker = Waker::from(Arc::new(CountingWaker::default()));
+        let mut cx = Context::from_waker(&waker);
+
+        let mut push = pin!(bffl.push_async(3));
+        assert_eq!(push.as_mut().poll(&mut cx), Poll::Pending);
+        let mut push_slice = pin!(bffl.push_slice_async(&[4, 5]));
+        assert_eq!(push_slice.as_mut().poll(&mut cx), Poll::Pending);
+        assert_eq!(bffl.dropped(), 0);
+
+        assert_eq!(bffl.read_slice(&mut [0; 2]), 2);
+        assert_eq!(push.as_mut().poll(&mut cx), Poll::Ready(()));
+        assert_eq!(push_slice.as_mut().poll(&mut cx), Poll::Ready(1));
+        assert_eq!(bffl.dropped(), 0);
+        #[cfg(feature = "stats")]
+        assert_eq!(bffl.stats().rejected, 0);
+    }
+
+    #[test]
+    fn async_producer_consumer_threads() {
+        const COUNT: usize = if cfg!(miri) { 100 } else { 5000 };
+        let bffl = Ringu::<u8, 8>::new();
+
+        thread::scope(|scope| {
+            scope.spawn(|| block_on(async {
+                let src: Vec<u8> = (0..COUNT).map(|i| i as u8).collect();
+                let mut sent = 0;
+                while sent < COUNT {
+                    if sent % 2 == 0 {
+                        bffl.push_async(src[sent]).await;
+                        sent += 1;
+                    }
+                    else {
+                        sent += bffl.push_slice_async(&src[sent..(sent + 5).min(COUNT)]).await;
+                    }
+                }
+            }));
+
+            block_on(async {
+                let mut received = 0;
+                let mut dst = [0u8; 3];
+                while received < COUNT {
+                    if received % 3 == 0 {
+                        assert_eq!(bffl.pop_async().await, received as u8);
+                        received += 1;
+                    }
+                    else {
+                        let nread = bffl.read_slice_async(&mut dst).await;
+                        for (i, val) in dst[..nread].iter().enumerate() {
+                            assert_eq!(*val, (received + i) as u8);
+                        }
+                        received += nread;
+                    }
+                }
+            });
+        });
+    }
+}
@@ -0,0 +1,217 @@
+/*
+Copyright (c) 2022 Todd Stellanova
+LICENSE: BSD3 (see LICENSE file)
+*/
+
+//! Loom models of the concurrent paths, checked over every interleaving.
+//!
+//! ```text
+//! RUSTFLAGS="--cfg loom" cargo test --test loom --release
+//! ```
+
+#![cfg(loom)]
+
+use loom::sync::Arc;
+use loom::thread;
+
+use ringu::{MpmcRingu, Overflow, Ringu};
+
+/// A split producer and consumer hand elements over in order,
+/// across the wrap point and through full and empty buffers
+#[test]
+fn spsc_split_in_order() {
+    loom::model(|| {
+        // the halves borrow the buffer, and loom threads need 'static borrows
+        let bffl: &'static mut Ringu<u8, 2> = Box::leak(Box::new(Ringu::new()));
+        let (mut producer, mut consumer) = bffl.split();
+        let writer = thread::spawn(move || {
+            for val in 1..=3 {
+                while producer.try_push(val).is_err() {
+                    thread::yield_now();
+                }
+            }
+        });
+        for val in 1..=3 {
+            loop {
+                match consumer.try_pop() {
+                    Some(got) => {
+                        assert_eq!(got, val);
+                        break;
+                    }
+                    None => thread::yield_now(),
+                }
+            }
+        }
+        writer.join().unwrap();
+        assert!(consumer.empty());
+    });
+}
+
+/// Two writers share a buffer through the lock while a reader pops;
+/// nothing is lost or duplicated
+#[test]
+fn mpsc_shared_push() {
+    loom::model(|| {
+        let bffl = Arc::new(Ringu::<u8, 2>::new());
+        let writers: Vec<_> = [1, 2].into_iter().map(|val| {
+            let bffl = bffl.clone();
+            thread::spawn(move || {
+                assert_eq!(bffl.try_push(val), Ok(()));
+            })
+        }).collect();
+        let mut got: Vec<u8> = bffl.try_pop().into_iter().collect();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+        got.extend(bffl.drain());
+        got.sort_unstable();
+        assert_eq!(got, [1, 2]);
+    });
+}
+
+/// Two writers and two readers race on the lock-free queue
+#[test]
+fn mpmc_lock_free() {
+    loom::model(|| {
+        let bffl = Arc::new(MpmcRingu::<2>::new());
+        let writers: Vec<_> = [1, 2].into_iter().map(|val| {
+            let bffl = bffl.clone();
+            thread::spawn(move || {
+                assert_eq!(bffl.push_one(val), 1);
+            })
+        }).collect();
+        let reader = {
+            let bffl = bffl.clone();
+            thread::spawn(move || bffl.read_one())
+        };
+        let mine = bffl.read_one();
+        let theirs = reader.join().unwrap();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+        let mut got = Vec::new();
+        for (count, val) in [mine, theirs, bffl.read_one(), bffl.read_one()] {
+            if count == 1 {
+                got.push(val);
+            }
+        }
+        got.sort_unstable();
+        assert_eq!(got, [1, 2]);
+        assert!(bffl.empty());
+    });
+}
+
+/// A writer filling the buffer and a reader emptying it agree on the count,
+/// and `available` never reports more than the capacity in between
+#[test]
+fn full_and_empty_boundaries() {
+    loom::model(|| {
+        let bffl = Arc::new(Ringu::<u8, 2>::new());
+        let writer = {
+            let bffl = bffl.clone();
+            thread::spawn(move || bffl.push_slice(&[1, 2, 3]))
+        };
+        let mut got = [0; 2];
+        let read = bffl.read_slice(&mut got);
+        let avail = bffl.available();
+        let written = writer.join().unwrap();
+        assert_eq!(written, 2);
+        assert!(avail <= 2);
+        assert_eq!(&got[..read], &[1, 2][..read]);
+        assert_eq!(bffl.available(), written - read);
+        assert_eq!(bffl.full(), read == 0);
+    });
+}
+
+/// An overwriting writer laps a concurrent reader across the wrap point;
+/// the reader only ever sees increasing values, and every element is
+/// either read, dropped or still buffered
+#[test]
+fn overwrite_wraparound() {
+    loom::model(|| {
+        let bffl = Arc::new(Ringu::<u8, 2>::new_with_overflow(Overflow::OverwriteOldest));
+        let writer = {
+            let bffl = bffl.clone();
+            thread::spawn(move || {
+                for val in 1..=3 {
+                    assert_eq!(bffl.push_one(val), 1);
+                }
+            })
+        };
+        let mut last = 0;
+        let mut read = 0;
+        for _ in 0..2 {
+            if let Some(val) = bffl.try_pop() {
+                assert!(val > last);
+                last = val;
+                read += 1;
+            }
+        }
+        writer.join().unwrap();
+        assert_eq!(read + bffl.dropped() + bffl.available(), 3);
+    });
+}
+
+/// A split producer and consumer hand elements over across the point
+/// where the unbounded indices wrap around `usize::MAX`
+#[test]
+fn spsc_index_wraparound() {
+    loom::model(|| {
+        let bffl: &'static mut Ringu<u8, 2> = Box::leak(Box::new(Ringu::new()));
+        bffl.start_at(usize::MAX);
+        let (mut producer, mut consumer) = bffl.split();
+        let writer = thread::spawn(move || {
+            for val in 1..=3 {
+                while producer.try_push(val).is_err() {
+                    thread::yield_now();
+                }
+            }
+        });
+        for val in 1..=3 {
+            loop {
+                match consumer.try_pop() {
+                    Some(got) => {
+                        assert_eq!(got, val);
+                        break;
+                    }
+                    None => thread::yield_now(),
+                }
+            }
+        }
+        writer.join().unwrap();
+        assert!(consumer.empty());
+    });
+}
+
+/// The lock-free queue's signed sequence arithmetic holds up
+/// while its indices wrap around `usize::MAX`
+#[test]
+fn mpmc_index_wraparound() {
+    loom::model(|| {
+        let mut bffl = MpmcRingu::<2>::new();
+        bffl.start_at(usize::MAX);
+        let bffl = Arc::new(bffl);
+        let writers: Vec<_> = [1, 2].into_iter().map(|val| {
+            let bffl = bffl.clone();
+            thread::spawn(move || {
+                assert_eq!(bffl.push_one(val), 1);
+            })
+        }).collect();
+        let (count, first) = bffl.read_one();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+        // the slot a read frees is writable again one lap later
+        assert_eq!(bffl.push_one(3), count);
+        let mut got = Vec::new();
+        if count == 1 {
+            got.push(first);
+        }
+        while let (1, val) = bffl.read_one() {
+            got.push(val);
+        }
+        got.sort_unstable();
+        assert_eq!(got, if count == 1 { &[1, 2, 3][..] } else { &[1, 2][..] });
+        assert!(bffl.empty());
+    });
+}